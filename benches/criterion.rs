@@ -0,0 +1,41 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use criterion::black_box;
+use std::collections::{BTreeMap, HashMap};
+
+use criterion::{criterion_group, criterion_main};
+use crossroads::crossroads;
+
+#[crossroads(bench = criterion)]
+fn filled() -> usize {
+    type Map = fork_type!(hash = HashMap<usize, usize>, btree = BTreeMap<usize, usize>);
+
+    let mut map = Map::default();
+    for key in 0..black_box(fork!(in [16, 1024] as keys)) {
+        map.insert(key, key);
+    }
+    map.len()
+}
+
+criterion_group!(benches, filled);
+criterion_main!(benches);
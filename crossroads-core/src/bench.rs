@@ -0,0 +1,114 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Ident, ItemFn, Visibility};
+
+use crate::output::is_plain;
+use crate::Path;
+
+/// The name of the benchmark for a path, i.e. its segments, or the name of the function if there are none.
+fn benchmark_name(original: &ItemFn, path: &Path) -> String {
+    if path.segments().is_empty() {
+        original.sig.ident.to_string()
+    } else {
+        path.segments().join("_")
+    }
+}
+
+fn check_plain(original: &ItemFn, harness: &str) -> syn::Result<()> {
+    if is_plain(&original.sig) {
+        return Ok(());
+    }
+    Err(syn::Error::new(
+        original.sig.span(),
+        format!(
+            "#[crossroads(bench = {})] can only be used with functions without arguments, generic parameters or async.",
+            harness
+        ),
+    ))
+}
+
+/// Builds a single function that registers a ```criterion``` benchmark group named after the function, with
+/// a benchmark per path. The generated functions are nested within it.
+pub(crate) fn criterion(
+    original: &ItemFn,
+    paths: &[Path],
+    functions: &[ItemFn],
+) -> syn::Result<TokenStream> {
+    check_plain(original, "criterion")?;
+
+    let attrs = original
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("cfg") || attr.path.is_ident("doc"));
+    let vis = &original.vis;
+    let name = &original.sig.ident;
+    let group = name.to_string();
+    let names = paths.iter().map(|path| benchmark_name(original, path));
+    let idents = functions.iter().map(|function| &function.sig.ident);
+    let functions = functions.iter().map(|function| ItemFn {
+        vis: Visibility::Inherited,
+        ..function.clone()
+    });
+    Ok(quote! {
+        #(#attrs)*
+        #vis fn #name(criterion: &mut ::criterion::Criterion) {
+            #(#functions)*
+
+            let mut group = criterion.benchmark_group(#group);
+            #(group.bench_function(#names, |bencher| bencher.iter(#idents));)*
+            group.finish();
+        }
+    })
+}
+
+/// Builds a module named after the function that contains a ```divan``` benchmark per path, named after
+/// its segments.
+pub(crate) fn divan(
+    original: &ItemFn,
+    paths: &[Path],
+    functions: &[ItemFn],
+) -> syn::Result<TokenStream> {
+    check_plain(original, "divan")?;
+
+    let vis = &original.vis;
+    let name = &original.sig.ident;
+    let functions = paths.iter().zip(functions).map(|(path, function)| {
+        let mut function = function.clone();
+        function.sig.ident = Ident::new(&benchmark_name(original, path), function.sig.ident.span());
+        function
+    });
+    Ok(quote! {
+        #vis mod #name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #(
+                #[::divan::bench]
+                #functions
+            )*
+        }
+    })
+}
@@ -0,0 +1,794 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::Span;
+use syn::ext::IdentExt;
+use syn::parse::discouraged::Speculative;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{
+    braced, bracketed, parenthesized, parse_quote, token, visit, visit_mut, Block, Expr, ExprBlock,
+    ExprMacro, ExprPath, Ident, Item, ItemFn, ItemImpl, LitInt, Local, Macro, Pat, Path, Signature,
+    Stmt, Token, Type,
+};
+
+use crate::hooks::arm_hooks;
+use crate::names::to_snake_case;
+
+/// One of the possible choices at a fork point: The segment it contributes to the name of the
+/// generated function and the expression (or type) that the fork point is replaced with.
+pub(crate) struct Alternative<T = Expr> {
+    pub(crate) segment: String,
+    pub(crate) replacement: T,
+}
+
+/// A single ```name = Type``` within ```fork_type!( .. )```.
+struct NamedType {
+    name: Ident,
+    ty: Type,
+}
+
+impl Parse for NamedType {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = Ident::parse_any(input)?;
+        input.parse::<Token![=]>()?;
+        let ty = input.parse()?;
+        Ok(Self { name, ty })
+    }
+}
+
+/// The contents of a ```fork!(Mode::{Fast, Safe})``` invocation, i.e. the path to an enum and
+/// the list of its variants that should each get their own function.
+pub(crate) struct Variants {
+    path: Path,
+    variants: Punctuated<Ident, Token![,]>,
+}
+
+impl Parse for Variants {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut path = Path {
+            leading_colon: input.parse()?,
+            segments: Punctuated::new(),
+        };
+        loop {
+            path.segments.push_value(Ident::parse_any(input)?.into());
+            let separator: Token![::] = input.parse()?;
+            if input.peek(token::Brace) {
+                break;
+            }
+            path.segments.push_punct(separator);
+        }
+
+        let content;
+        braced!(content in input);
+        let variants = content.parse_terminated(Ident::parse)?;
+
+        Ok(Self { path, variants })
+    }
+}
+
+/// The largest number of values that ```fork!(in ..)``` accepts.
+const MAX_VALUES: usize = 1024;
+
+/// A bound of an integer range within ```fork!(in 0..=3)```, which has to be a literal.
+struct Bound {
+    value: i128,
+    suffix: String,
+}
+
+impl Parse for Bound {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let negative = input.parse::<Option<Token![-]>>()?.is_some();
+        let literal: LitInt = input.parse().map_err(|error| {
+            syn::Error::new(
+                error.span(),
+                "The bounds of a range in fork!(in ..) must be integer literals.",
+            )
+        })?;
+        let value: i128 = literal.base10_parse()?;
+        Ok(Self {
+            value: if negative { -value } else { value },
+            suffix: literal.suffix().to_owned(),
+        })
+    }
+}
+
+/// The contents of a ```fork!(in 0..=3 as n)``` or ```fork!(in [a, b] as n)``` invocation, i.e. the
+/// values that each get their own function along with their names. The name of the fork point
+/// defaults to the binding of the surrounding ```let``` statement.
+struct Values {
+    values: Vec<(String, Expr)>,
+    name: Option<Ident>,
+}
+
+impl Parse for Values {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<Token![in]>()?;
+        let values = if input.peek(token::Bracket) {
+            let content;
+            bracketed!(content in input);
+            content
+                .parse_terminated::<Expr, Token![,]>(Expr::parse)?
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| (index.to_string(), value))
+                .collect()
+        } else {
+            let start: Bound = input.parse()?;
+            let inclusive = if input.peek(Token![..=]) {
+                input.parse::<Token![..=]>()?;
+                true
+            } else {
+                input.parse::<Token![..]>()?;
+                false
+            };
+            let end_span = input.span();
+            let end: Bound = input.parse()?;
+            // A suffix on either bound determines the type of all values, e.g. for 254..=255u8.
+            let suffix = match (start.suffix.as_str(), end.suffix.as_str()) {
+                (start, "") => start.to_owned(),
+                ("", end) => end.to_owned(),
+                (start, end) if start == end => start.to_owned(),
+                (start, end) => {
+                    return Err(syn::Error::new(
+                        end_span,
+                        format!(
+                            "The bounds of a range in fork!(in ..) must have the same suffix, found `{}` and `{}`.",
+                            start, end
+                        ),
+                    ))
+                }
+            };
+            let end = if inclusive { end.value } else { end.value - 1 };
+            if end.saturating_sub(start.value) >= MAX_VALUES as i128 {
+                return Err(input.error(format!(
+                    "Cannot use more than {} values with fork!(in ..), as that results in too many paths.",
+                    MAX_VALUES
+                )));
+            }
+
+            (start.value..=end)
+                .map(|value| {
+                    // Not using the span of the bounds, as lints would otherwise look at their source.
+                    let literal = LitInt::new(
+                        &format!("{}{}", value.unsigned_abs(), suffix),
+                        Span::call_site(),
+                    );
+                    if value < 0 {
+                        (
+                            format!("minus_{}", value.unsigned_abs()),
+                            parse_quote!(-#literal),
+                        )
+                    } else {
+                        (value.to_string(), Expr::Lit(parse_quote!(#literal)))
+                    }
+                })
+                .collect()
+        };
+
+        let name = if input.parse::<Option<Token![as]>>()?.is_some() {
+            Some(Ident::parse_any(input)?)
+        } else {
+            None
+        };
+        Ok(Self { values, name })
+    }
+}
+
+/// Whether the macro is a ```fork!(in ..)``` invocation.
+fn is_value_fork(mac: &Macro) -> bool {
+    is_fork(mac)
+        && matches!(mac.tokens.clone().into_iter().next(), Some(proc_macro2::TokenTree::Ident(ident)) if ident == "in")
+}
+
+/// The largest number of statements that ```fork_permutations!``` accepts, as their orderings grow
+/// factorially: 8 statements already make for 40320 paths.
+const MAX_PERMUTED: usize = 8;
+
+/// The largest number of statements that ```fork_subsets!``` accepts, resulting in 1024 paths.
+const MAX_OPTIONAL: usize = 10;
+
+/// The largest number of interleavings that ```fork_interleave!``` may result in.
+const MAX_INTERLEAVINGS: usize = 40320;
+
+/// A statement within ```fork_permutations! { .. }``` or ```fork_subsets! { .. }```, optionally named by
+/// a preceding ```#[label(name)]```.
+struct Labeled {
+    label: Option<Ident>,
+    stmt: Stmt,
+}
+
+/// The contents of a ```fork_permutations! { .. }``` or ```fork_subsets! { .. }``` invocation.
+struct Statements(Vec<Labeled>);
+
+impl Statements {
+    /// The names of the statements as used in the names of the generated functions.
+    fn names(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(index, labeled)| match &labeled.label {
+                Some(label) => label.to_string(),
+                None => format!("s{}", index),
+            })
+            .collect()
+    }
+
+    /// A block of the statements with the given indices, in that order.
+    fn select(&self, mac: &ExprMacro, indices: &[usize]) -> Expr {
+        Expr::Block(ExprBlock {
+            attrs: mac.attrs.clone(),
+            label: None,
+            block: Block {
+                brace_token: Default::default(),
+                stmts: indices
+                    .iter()
+                    .map(|&index| self.0[index].stmt.clone())
+                    .collect(),
+            },
+        })
+    }
+}
+
+impl Parse for Statements {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut stmts = Vec::new();
+        while !input.is_empty() {
+            while input.parse::<Option<Token![;]>>()?.is_some() {}
+            if input.is_empty() {
+                break;
+            }
+
+            let ahead = input.fork();
+            let label = if ahead.peek(Token![#]) && ahead.peek2(token::Bracket) {
+                let content;
+                ahead.parse::<Token![#]>()?;
+                bracketed!(content in ahead);
+                match content.parse::<Ident>()? {
+                    name if name == "label" => {
+                        let label;
+                        parenthesized!(label in content);
+                        input.advance_to(&ahead);
+                        Some(label.parse()?)
+                    }
+                    _ => None,
+                }
+            } else {
+                None
+            };
+
+            let stmt = detached(input.parse()?);
+            stmts.push(Labeled { label, stmt });
+        }
+        Ok(Self(stmts))
+    }
+}
+
+/// Terminates expression statements with a semicolon, as statements that are reordered may end up in
+/// any position, so none of them can be the value of the surrounding block.
+fn detached(stmt: Stmt) -> Stmt {
+    match stmt {
+        Stmt::Expr(expr) => Stmt::Semi(expr, Default::default()),
+        stmt => stmt,
+    }
+}
+
+/// A named sequence of statements within ```fork_interleave! { .. }```.
+struct Sequence {
+    name: Ident,
+    stmts: Vec<Stmt>,
+}
+
+/// The contents of a ```fork_interleave! { a: { .. }, b: { .. } }``` invocation.
+struct Sequences(Punctuated<Sequence, Token![,]>);
+
+impl Parse for Sequence {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let content;
+        braced!(content in input);
+        let stmts = content
+            .call(Block::parse_within)?
+            .into_iter()
+            .map(detached)
+            .collect();
+        Ok(Self { name, stmts })
+    }
+}
+
+impl Parse for Sequences {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse_terminated(Sequence::parse).map(Self)
+    }
+}
+
+/// All ways to interleave sequences of the given lengths while keeping the order within each of them,
+/// as the index of the sequence that takes each step. Earlier sequences go first.
+fn interleavings(remaining: &mut [usize]) -> Vec<Vec<usize>> {
+    let mut orderings = Vec::new();
+    for sequence in 0..remaining.len() {
+        if remaining[sequence] == 0 {
+            continue;
+        }
+
+        remaining[sequence] -= 1;
+        for rest in interleavings(remaining) {
+            let mut ordering = vec![sequence];
+            ordering.extend(rest);
+            orderings.push(ordering);
+        }
+        remaining[sequence] += 1;
+    }
+
+    if orderings.is_empty() {
+        orderings.push(Vec::new());
+    }
+    orderings
+}
+
+/// The number of ways to interleave sequences of the given lengths, if it does not exceed ```limit```.
+fn count_interleavings(lengths: &[usize], limit: usize) -> Option<usize> {
+    // The multinomial coefficient, built up as a product of binomial coefficients.
+    let mut count: usize = 1;
+    let mut total = 0;
+    for &length in lengths {
+        for taken in 1..=length {
+            total += 1;
+            count = count.checked_mul(total)? / taken;
+        }
+    }
+    Some(count).filter(|&count| count <= limit)
+}
+
+/// All orderings of ```0..n```, in lexicographic order.
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![vec![]];
+    }
+
+    let mut orderings = Vec::new();
+    for first in 0..n {
+        for rest in permutations(n - 1) {
+            let mut ordering = vec![first];
+            ordering.extend(
+                rest.into_iter()
+                    .map(|index| index + usize::from(index >= first)),
+            );
+            orderings.push(ordering);
+        }
+    }
+    orderings
+}
+
+// TODO: Proper handling of namespace..
+// match mac.path.segments.iter().map(|segment| segment.ident.to_string()).collect::<Vec<String>>().as_ref::<[&str]>() {
+//     ["fork"] | ["crossroads", "fork"] => {}
+//     _ => {}
+// }
+fn is_macro(mac: &Macro, name: &str) -> bool {
+    matches!(mac.path.segments.first(), Some(segment) if segment.ident == name)
+}
+
+pub(crate) fn is_fork(mac: &Macro) -> bool {
+    is_macro(mac, "fork")
+}
+
+/// Whether the macro is one of the fork points that are usually invoked as a statement, i.e.
+/// ```fork_permutations! { .. }```, ```fork_subsets! { .. }``` and ```fork_interleave! { .. }```.
+fn is_statement_fork(mac: &Macro) -> bool {
+    ["fork_permutations", "fork_subsets", "fork_interleave"]
+        .iter()
+        .any(|name| is_macro(mac, name))
+}
+
+/// Turns fork points invoked as statements into expression statements, which is where the remaining
+/// code expects them (```syn``` parses macros in statement position as items).
+struct StatementForks;
+
+impl VisitMut for StatementForks {
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        if let Stmt::Item(Item::Macro(item)) = stmt {
+            if item.ident.is_none() && is_statement_fork(&item.mac) {
+                let expr = Expr::Macro(ExprMacro {
+                    attrs: item.attrs.clone(),
+                    mac: item.mac.clone(),
+                });
+                *stmt = match item.semi_token {
+                    Some(semi) => Stmt::Semi(expr, semi),
+                    None => Stmt::Expr(expr),
+                };
+            }
+        }
+        visit_mut::visit_stmt_mut(self, stmt);
+    }
+
+    fn visit_local_mut(&mut self, local: &mut Local) {
+        // A fork!(in ..) without a name of its own is named after the variable it is bound to.
+        if let Some((_, init)) = &mut local.init {
+            if let Expr::Macro(mac) = init.as_mut() {
+                let ident = match &local.pat {
+                    Pat::Ident(pat) => Some(&pat.ident),
+                    Pat::Type(pat) => match pat.pat.as_ref() {
+                        Pat::Ident(pat) => Some(&pat.ident),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let unnamed = is_value_fork(&mac.mac)
+                    && matches!(mac.mac.parse_body::<Values>(), Ok(values) if values.name.is_none());
+                if let (Some(ident), true) = (ident, unnamed) {
+                    let ident =
+                        Ident::new(ident.to_string().trim_start_matches("r#"), ident.span());
+                    mac.mac.tokens.extend(quote::quote!(as #ident));
+                }
+            }
+        }
+        visit_mut::visit_local_mut(self, local);
+    }
+}
+
+/// Prepares a function for the search for fork points, i.e. turns fork points invoked as statements
+/// into expressions and names the ```fork!(in ..)```s that are bound to variables.
+pub(crate) fn normalize(function: &ItemFn) -> ItemFn {
+    let mut function = function.clone();
+    StatementForks.visit_item_fn_mut(&mut function);
+    function
+}
+
+/// Prepares all methods of an impl block for the search for fork points, see [normalize].
+pub(crate) fn normalize_impl(item: &ItemImpl) -> ItemImpl {
+    let mut item = item.clone();
+    StatementForks.visit_item_impl_mut(&mut item);
+    item
+}
+
+/// Parses the statements of a ```fork_permutations! { .. }``` or ```fork_subsets! { .. }``` invocation.
+fn statements(mac: &ExprMacro, limit: usize) -> Statements {
+    let name = &mac.mac.path.segments[0].ident;
+    let statements: Statements = mac.mac.parse_body().unwrap_or_else(|error| {
+        panic!(
+            "Expected a list of statements within {}! {{ .. }}: {} {:?}",
+            name,
+            error,
+            mac.span()
+        )
+    });
+    assert!(
+        !statements.0.is_empty(),
+        "Must list at least one statement with {}!()! {:?}",
+        name,
+        mac.span()
+    );
+    assert!(
+        statements.0.len() <= limit,
+        "Cannot use more than {} statements with {}!(), as that results in too many paths. {:?}",
+        limit,
+        name,
+        mac.span()
+    );
+    statements
+}
+
+/// One alternative per ordering of the statements in a ```fork_permutations! { .. }``` invocation.
+fn permutation_alternatives(mac: &ExprMacro) -> Vec<Alternative> {
+    let statements = statements(mac, MAX_PERMUTED);
+    let names = statements.names();
+
+    permutations(statements.0.len())
+        .into_iter()
+        .map(|ordering| Alternative {
+            segment: ordering
+                .iter()
+                .map(|&index| names[index].as_str())
+                .collect::<Vec<_>>()
+                .join("_"),
+            replacement: statements.select(mac, &ordering),
+        })
+        .collect()
+}
+
+/// One alternative per subset of the statements in a ```fork_subsets! { .. }``` invocation, each
+/// keeping the statements in their original order.
+fn subset_alternatives(mac: &ExprMacro) -> Vec<Alternative> {
+    let statements = statements(mac, MAX_OPTIONAL);
+    let names = statements.names();
+
+    (0..1usize << statements.0.len())
+        .map(|mask| {
+            let subset: Vec<usize> = (0..statements.0.len())
+                .filter(|index| mask & (1 << index) != 0)
+                .collect();
+            let segment = if subset.is_empty() {
+                String::from("with_none")
+            } else {
+                let names: Vec<&str> = subset.iter().map(|&index| names[index].as_str()).collect();
+                format!("with_{}", names.join("_"))
+            };
+            Alternative {
+                segment,
+                replacement: statements.select(mac, &subset),
+            }
+        })
+        .collect()
+}
+
+/// One alternative per interleaving of the sequences in a ```fork_interleave! { .. }``` invocation,
+/// named after the sequence that takes each step, e.g. ```a_b_b_a```.
+fn interleave_alternatives(mac: &ExprMacro) -> Vec<Alternative> {
+    let Sequences(sequences) = mac.mac.parse_body().unwrap_or_else(|error| {
+        panic!(
+            "Expected a list of named sequences such as fork_interleave! {{ a: {{ .. }}, b: {{ .. }} }}: {} {:?}",
+            error,
+            mac.span()
+        )
+    });
+    assert!(
+        !sequences.is_empty(),
+        "Must list at least one sequence with fork_interleave!()! {:?}",
+        mac.span()
+    );
+
+    let mut lengths: Vec<usize> = sequences
+        .iter()
+        .map(|sequence| sequence.stmts.len())
+        .collect();
+    assert!(
+        count_interleavings(&lengths, MAX_INTERLEAVINGS).is_some(),
+        "Cannot interleave sequences with more than {} interleavings with fork_interleave!(), as that results in too many paths. {:?}",
+        MAX_INTERLEAVINGS,
+        mac.span()
+    );
+
+    interleavings(&mut lengths)
+        .into_iter()
+        .map(|ordering| {
+            let mut steps = vec![0; sequences.len()];
+            let mut stmts = Vec::with_capacity(ordering.len());
+            for &sequence in &ordering {
+                stmts.push(sequences[sequence].stmts[steps[sequence]].clone());
+                steps[sequence] += 1;
+            }
+
+            Alternative {
+                segment: ordering
+                    .iter()
+                    .map(|&sequence| sequences[sequence].name.to_string())
+                    .collect::<Vec<_>>()
+                    .join("_"),
+                replacement: Expr::Block(ExprBlock {
+                    attrs: mac.attrs.clone(),
+                    label: None,
+                    block: Block {
+                        brace_token: Default::default(),
+                        stmts,
+                    },
+                }),
+            }
+        })
+        .collect()
+}
+
+/// Returns the alternatives if the given type is a fork point, i.e. a
+/// ```fork_type!(hash = HashMap<K, V>, btree = BTreeMap<K, V>)```.
+pub(crate) fn type_alternatives(ty: &Type) -> Option<Vec<Alternative<Type>>> {
+    match ty {
+        Type::Macro(mac) if is_macro(&mac.mac, "fork_type") => {
+            let types = mac
+                .mac
+                .parse_body_with(Punctuated::<NamedType, Token![,]>::parse_terminated)
+                .unwrap_or_else(|error| {
+                    panic!(
+                        "Expected a list of named types such as fork_type!(hash = HashMap<K, V>, btree = BTreeMap<K, V>): {} {:?}",
+                        error,
+                        mac.span()
+                    )
+                });
+            assert!(
+                !types.is_empty(),
+                "Must list at least one type with fork_type!()! {:?}",
+                mac.span()
+            );
+            Some(
+                types
+                    .into_iter()
+                    .map(|named| Alternative {
+                        segment: named.name.to_string().trim_start_matches("r#").to_owned(),
+                        replacement: named.ty,
+                    })
+                    .collect(),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Panics if the function contains ```fork_type!()```s, which require a function per path.
+pub(crate) fn reject_type_forks(function: &ItemFn, option: &str) {
+    struct TypeForks<'a>(&'a str);
+
+    impl<'ast> Visit<'ast> for TypeForks<'_> {
+        fn visit_type(&mut self, ty: &'ast Type) {
+            assert!(
+                type_alternatives(ty).is_none(),
+                "fork_type!() cannot be used with #[crossroads({})], as that keeps a single function for all paths. {:?}",
+                self.0,
+                ty.span()
+            );
+            visit::visit_type(self, ty);
+        }
+    }
+
+    TypeForks(option).visit_item_fn(function);
+}
+
+/// The first ```fork_type!()``` within the signature, if any.
+pub(crate) fn signature_type_fork(sig: &Signature) -> Option<&Type> {
+    struct FirstTypeFork<'ast>(Option<&'ast Type>);
+
+    impl<'ast> Visit<'ast> for FirstTypeFork<'ast> {
+        fn visit_type(&mut self, ty: &'ast Type) {
+            if self.0.is_none() && type_alternatives(ty).is_some() {
+                self.0 = Some(ty);
+            }
+            visit::visit_type(self, ty);
+        }
+    }
+
+    let mut fork = FirstTypeFork(None);
+    fork.visit_signature(sig);
+    fork.0
+}
+
+/// The segment for an enum variant, which consists of the name of the enum and the name of the
+/// variant, i.e. ```Mode::Fast``` turns into ```mode_fast```.
+pub(crate) fn variant_segment(path: &Path) -> String {
+    path.segments
+        .iter()
+        .rev()
+        .take(2)
+        .rev()
+        .map(|segment| to_snake_case(&segment.ident))
+        .collect::<Vec<String>>()
+        .join("_")
+}
+
+pub(crate) fn arm_segment(pat: &Pat) -> String {
+    match pat {
+        Pat::Ident(ident) => ident.ident.to_string(),
+        Pat::Path(path) if path.qself.is_none() => variant_segment(&path.path),
+        _ => panic!(
+            "Must use only idents or paths to enum variants with a fork!() match! {:?}",
+            pat.span()
+        ),
+    }
+}
+
+/// Returns the alternatives if the given expression is a fork point, i.e. either a
+/// ```match fork!() { .. }```, a ```fork!(Mode::{ .. })```, a ```fork!(in ..)```, a ```fork_permutations! { .. }```, a
+/// ```fork_subsets! { .. }``` or a ```fork_interleave! { .. }``` expression.
+pub(crate) fn alternatives(expr: &Expr) -> Option<Vec<Alternative>> {
+    match expr {
+        Expr::Macro(mac) if is_macro(&mac.mac, "fork_permutations") => {
+            Some(permutation_alternatives(mac))
+        }
+        Expr::Macro(mac) if is_macro(&mac.mac, "fork_subsets") => Some(subset_alternatives(mac)),
+        Expr::Macro(mac) if is_macro(&mac.mac, "fork_interleave") => {
+            Some(interleave_alternatives(mac))
+        }
+        Expr::Match(mtch) => match mtch.expr.as_ref() {
+            Expr::Macro(mac) if is_fork(&mac.mac) && mac.mac.tokens.is_empty() => {
+                assert!(
+                    !mtch.arms.is_empty(),
+                    "Must have at least one branch in match branches with fork!()! {:?}",
+                    mtch.span()
+                );
+                Some(
+                    mtch.arms
+                        .iter()
+                        .map(|arm| Alternative {
+                            segment: arm_segment(&arm.pat),
+                            replacement: Expr::Block(ExprBlock {
+                                attrs: mtch.attrs.clone(),
+                                label: None,
+                                block: arm_hooks(
+                                    &arm.attrs,
+                                    Block {
+                                        brace_token: Default::default(),
+                                        stmts: vec![Stmt::Expr(Expr::clone(arm.body.as_ref()))],
+                                    },
+                                ),
+                            }),
+                        })
+                        .collect(),
+                )
+            }
+            _ => None,
+        },
+        Expr::Macro(mac) if is_value_fork(&mac.mac) => {
+            let values: Values = mac.mac.parse_body().unwrap_or_else(|error| {
+                panic!(
+                    "Expected a range of integer literals or a list of values such as fork!(in 0..=3) or fork!(in [a, b] as name): {} {:?}",
+                    error,
+                    mac.span()
+                )
+            });
+            let name = values.name.unwrap_or_else(|| {
+                panic!(
+                    "fork!(in ..) must either be bound to a variable with let or be named with fork!(in .. as name)! {:?}",
+                    mac.span()
+                )
+            });
+            assert!(
+                !values.values.is_empty(),
+                "Must list at least one value with fork!(in ..)! {:?}",
+                mac.span()
+            );
+            Some(
+                values
+                    .values
+                    .into_iter()
+                    .map(|(segment, value)| Alternative {
+                        segment: format!("{}_{}", name, segment),
+                        replacement: value,
+                    })
+                    .collect(),
+            )
+        }
+        Expr::Macro(mac) if is_fork(&mac.mac) => {
+            let variants: Variants = mac.mac.parse_body().unwrap_or_else(|error| {
+                panic!(
+                    "Expected a list of enum variants such as fork!(Mode::{{Fast, Safe}}): {} {:?}",
+                    error,
+                    mac.span()
+                )
+            });
+            assert!(
+                !variants.variants.is_empty(),
+                "Must list at least one variant with fork!()! {:?}",
+                mac.span()
+            );
+            Some(
+                variants
+                    .variants
+                    .iter()
+                    .map(|variant| {
+                        let mut path = variants.path.clone();
+                        path.segments.push(variant.clone().into());
+                        Alternative {
+                            segment: variant_segment(&path),
+                            replacement: Expr::Path(ExprPath {
+                                attrs: mac.attrs.clone(),
+                                qself: None,
+                                path,
+                            }),
+                        }
+                    })
+                    .collect(),
+            )
+        }
+        _ => None,
+    }
+}
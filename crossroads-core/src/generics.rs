@@ -0,0 +1,175 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::{Group, Spacing, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::visit_mut::VisitMut;
+use syn::{
+    parse_quote, visit_mut, ExprPath, GenericParam, Ident, ItemFn, Macro, Path, Type, TypePath,
+    WherePredicate,
+};
+
+use crate::names::type_segment;
+use crate::options::Instantiation;
+
+/// Replaces a generic type parameter with a concrete type.
+struct Substitution<'a> {
+    param: &'a Ident,
+    ty: &'a Type,
+}
+
+impl Substitution<'_> {
+    /// The remainder of a path such as ```T::new```, if it starts with the parameter.
+    fn associated(&self, path: &Path) -> Option<Path> {
+        if path.leading_colon.is_some()
+            || path.segments.len() < 2
+            || path.segments[0].ident != *self.param
+        {
+            return None;
+        }
+
+        let segments = path.segments.iter().skip(1);
+        Some(parse_quote!(#(#segments)::*))
+    }
+
+    /// Replaces the parameter within the arguments of a macro, which are not parsed and can only
+    /// be processed token by token.
+    fn substitute(&self, tokens: TokenStream) -> TokenStream {
+        let mut substituted = TokenStream::new();
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            match token {
+                TokenTree::Ident(ident) if ident == *self.param => {
+                    let ty = self.ty;
+                    let path = matches!(
+                        tokens.peek(),
+                        Some(TokenTree::Punct(punct)) if punct.as_char() == ':' && punct.spacing() == Spacing::Joint
+                    );
+                    // Paths such as T::new need the concrete type in angle brackets.
+                    if path {
+                        substituted.extend(quote::quote!(<#ty>));
+                    } else {
+                        ty.to_tokens(&mut substituted);
+                    }
+                }
+                TokenTree::Group(group) => {
+                    let mut inner = Group::new(group.delimiter(), self.substitute(group.stream()));
+                    inner.set_span(group.span());
+                    substituted.extend([TokenTree::Group(inner)]);
+                }
+                token => substituted.extend([token]),
+            }
+        }
+        substituted
+    }
+}
+
+impl VisitMut for Substitution<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(TypePath { qself: None, path }) = ty {
+            if path.is_ident(self.param) {
+                *ty = self.ty.clone();
+                return;
+            }
+            if let Some(rest) = self.associated(path) {
+                let concrete = self.ty;
+                *ty = parse_quote!(<#concrete>::#rest);
+                return;
+            }
+        }
+        visit_mut::visit_type_mut(self, ty);
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut Macro) {
+        mac.tokens = self.substitute(mac.tokens.clone());
+    }
+
+    fn visit_expr_path_mut(&mut self, expr: &mut ExprPath) {
+        if expr.qself.is_none() {
+            if let Some(rest) = self.associated(&expr.path) {
+                let concrete = self.ty;
+                let attrs = &expr.attrs;
+                *expr = parse_quote!(#(#attrs)* <#concrete>::#rest);
+                return;
+            }
+        }
+        visit_mut::visit_expr_path_mut(self, expr);
+    }
+}
+
+/// Creates a non-generic version of the function for every combination of the given types, along
+/// with the name segments of the types. The bounds of the parameters turn into ```where``` clauses on
+/// the concrete types, so that unsuitable types are still rejected.
+pub(crate) fn instantiate(
+    function: &ItemFn,
+    instantiations: &[Instantiation],
+) -> syn::Result<Vec<(Vec<String>, ItemFn)>> {
+    let mut instances = vec![(Vec::new(), function.clone())];
+    for instantiation in instantiations {
+        let param = &instantiation.param;
+        let bounds = function
+            .sig
+            .generics
+            .type_params()
+            .find(|type_param| type_param.ident == *param)
+            .map(|type_param| type_param.bounds.clone())
+            .ok_or_else(|| {
+                syn::Error::new(
+                    param.span(),
+                    format!(
+                        "The function has no generic type parameter named {}.",
+                        param
+                    ),
+                )
+            })?;
+
+        let mut new_instances = Vec::new();
+        for ty in &instantiation.types {
+            for (segments, instance) in &instances {
+                let mut instance = instance.clone();
+                let generics = &mut instance.sig.generics;
+                generics.params = std::mem::take(&mut generics.params)
+                    .into_iter()
+                    .filter(|generic| {
+                        !matches!(generic, GenericParam::Type(type_param) if type_param.ident == *param)
+                    })
+                    .collect();
+                if generics.params.is_empty() {
+                    generics.lt_token = None;
+                    generics.gt_token = None;
+                }
+                if !bounds.is_empty() {
+                    let predicate: WherePredicate = parse_quote!(#param: #bounds);
+                    generics.make_where_clause().predicates.push(predicate);
+                }
+
+                Substitution { param, ty }.visit_item_fn_mut(&mut instance);
+
+                let mut segments = segments.clone();
+                segments.push(type_segment(ty));
+                new_instances.push((segments, instance));
+            }
+        }
+        instances = new_instances;
+    }
+    Ok(instances)
+}
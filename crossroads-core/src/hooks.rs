@@ -0,0 +1,119 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_quote, Attribute, Block, Expr, ExprPath, ItemFn, ReturnType, Type};
+
+use crate::options::Wrapper;
+use crate::Path;
+
+/// Wraps the statements of a block so that ```before``` is called first and ```after``` is called once
+/// they are done, even if they panic or return early.
+pub(crate) fn guarded(before: Option<Expr>, after: Option<Expr>, block: &Block) -> Block {
+    let stmts = &block.stmts;
+    let before = before.map(|before| quote!(#before;));
+    let after = after.map(|after| {
+        // Mixed site hygiene keeps the guard out of reach of the wrapped statements.
+        let guard = syn::Ident::new("crossroads_after", Span::mixed_site());
+        let ty = syn::Ident::new("CrossroadsAfter", Span::mixed_site());
+        quote! {
+            struct #ty<F: ::core::ops::FnMut()>(F);
+            impl<F: ::core::ops::FnMut()> ::core::ops::Drop for #ty<F> {
+                fn drop(&mut self) {
+                    (self.0)()
+                }
+            }
+            let #guard = #ty(|| #after);
+        }
+    });
+    parse_quote!({
+        #before
+        #after
+        #(#stmts)*
+    })
+}
+
+/// Wraps a generated function in the ```before``` and ```after``` hooks, which receive its path.
+pub(crate) fn hooks(
+    function: &mut ItemFn,
+    before: Option<&ExprPath>,
+    after: Option<&ExprPath>,
+    path: &Path,
+) {
+    if before.is_none() && after.is_none() {
+        return;
+    }
+    let segments = path.segments();
+    let segments: TokenStream = quote!(&[#(#segments),*]);
+    *function.block = guarded(
+        before.map(|before| parse_quote!(#before(#segments))),
+        after.map(|after| parse_quote!(#after(#segments))),
+        &function.block,
+    );
+}
+
+/// Wraps the body of an arm in calls to the hooks given by its ```#[before(hook)]``` and
+/// ```#[after(hook)]``` attributes, if any.
+pub(crate) fn arm_hooks(attrs: &[Attribute], body: Block) -> Block {
+    let hook = |name: &str| -> Option<Expr> {
+        let attr = attrs.iter().find(|attr| attr.path.is_ident(name))?;
+        let hook: ExprPath = attr.parse_args().unwrap_or_else(|error| {
+            panic!(
+                "Expected the path of a function within #[{}(..)]: {} {:?}",
+                name,
+                error,
+                attr.span()
+            )
+        });
+        Some(parse_quote!(#hook()))
+    };
+    match (hook("before"), hook("after")) {
+        (None, None) => body,
+        (before, after) => guarded(before, after, &body),
+    }
+}
+
+/// Hands the body of a generated function to the wrapper as a closure, whose result is returned.
+pub(crate) fn wrap(function: &mut ItemFn, wrapper: &Wrapper) -> syn::Result<()> {
+    if let Some(asyncness) = &function.sig.asyncness {
+        // The closure would have to be async as well, which the wrappers can not be expected to handle.
+        return Err(syn::Error::new(
+            asyncness.span(),
+            "#[crossroads(wrap_with = ..)] cannot be used with async functions.",
+        ));
+    }
+    let block = &function.block;
+    // The return type lets ```?``` convert errors just like it would in the function itself.
+    let closure = match &function.sig.output {
+        ReturnType::Type(arrow, ty) if !matches!(ty.as_ref(), Type::ImplTrait(_)) => {
+            quote!(move || #arrow #ty #block)
+        }
+        _ => quote!(move || #block),
+    };
+    *function.block = match wrapper {
+        Wrapper::Function(wrapper) => parse_quote!({ #wrapper(#closure) }),
+        Wrapper::Macro(wrapper) => parse_quote!({ #wrapper!(#closure) }),
+    };
+    Ok(())
+}
@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{parse_quote, GenericParam, Generics, Ident, ItemImpl, Type};
+
+use crate::names::to_pascal_case;
+use crate::output::mentions;
+use crate::paths::{PathFinder, Rewriter};
+
+/// Builds one copy of an impl block per path through the fork points in its methods. Each of them
+/// is for a newtype around the original type, named after the path (e.g. ```Store_AfterAdd```), which
+/// dereferences to the original type.
+pub(crate) fn impls(original: &ItemImpl, max_paths: usize) -> syn::Result<TokenStream> {
+    let mut finder = PathFinder::new(vec![vec![]], max_paths);
+    finder.visit_item_impl(original);
+    let paths = finder.into_inner();
+    if paths == [Vec::<String>::new()] {
+        return Ok(original.to_token_stream());
+    }
+
+    let self_ty = &original.self_ty;
+    let name = match self_ty.as_ref() {
+        Type::Path(path) if path.qself.is_none() => &path.path.segments.last().unwrap().ident,
+        _ => {
+            return Err(syn::Error::new(
+                self_ty.span(),
+                "#[crossroads] can only be used with impl blocks for named types.",
+            ))
+        }
+    };
+    let generics = newtype_generics(&original.generics, self_ty);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut tokens = TokenStream::new();
+    for path in paths {
+        let newtype = Ident::new(
+            &format!(
+                "{}_{}",
+                name.to_string().trim_start_matches("r#"),
+                to_pascal_case(&path.join("_"))
+            ),
+            name.span(),
+        );
+
+        let mut new_impl = original.clone();
+        new_impl.self_ty = parse_quote!(#newtype #ty_generics);
+        Rewriter::new(path).visit_item_impl_mut(&mut new_impl);
+
+        let definition: TokenStream = parse_quote! {
+            #[allow(non_camel_case_types)]
+            struct #newtype #generics (#self_ty) #where_clause;
+
+            impl #impl_generics ::core::ops::Deref for #newtype #ty_generics #where_clause {
+                type Target = #self_ty;
+
+                fn deref(&self) -> &Self::Target {
+                    &self.0
+                }
+            }
+
+            impl #impl_generics ::core::ops::DerefMut for #newtype #ty_generics #where_clause {
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    &mut self.0
+                }
+            }
+
+            #new_impl
+        };
+        definition.to_tokens(&mut tokens);
+    }
+    Ok(tokens)
+}
+
+/// The generic parameters of the impl block that the newtype needs to carry, i.e. those that appear within
+/// the type it wraps. Parameters that are only used by the trait (e.g. ```impl<K> Backend<K> for Store```) would
+/// be unused otherwise, so they are dropped along with any bounds that refer to them.
+fn newtype_generics(generics: &Generics, self_ty: &Type) -> Generics {
+    let ident = |param: &GenericParam| match param {
+        GenericParam::Type(param) => param.ident.clone(),
+        GenericParam::Lifetime(param) => param.lifetime.ident.clone(),
+        GenericParam::Const(param) => param.ident.clone(),
+    };
+    let (kept, dropped): (Vec<GenericParam>, Vec<GenericParam>) = generics
+        .params
+        .iter()
+        .cloned()
+        .partition(|param| mentions(self_ty.to_token_stream(), &ident(param)));
+    let independent = |tokens: TokenStream| {
+        !dropped
+            .iter()
+            .any(|param| mentions(tokens.clone(), &ident(param)))
+    };
+
+    let mut newtype = generics.clone();
+    newtype.params = kept
+        .into_iter()
+        .map(|mut param| {
+            if let GenericParam::Type(param) = &mut param {
+                param.bounds = param
+                    .bounds
+                    .iter()
+                    .filter(|bound| independent(bound.to_token_stream()))
+                    .cloned()
+                    .collect();
+            }
+            param
+        })
+        .collect();
+    if let Some(where_clause) = &mut newtype.where_clause {
+        where_clause.predicates = where_clause
+            .predicates
+            .iter()
+            .filter(|predicate| independent(predicate.to_token_stream()))
+            .cloned()
+            .collect();
+    }
+    newtype
+}
@@ -0,0 +1,208 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{
+    parse_quote, visit, visit_mut, Attribute, ExprStruct, Field, FieldValue, Fields, Ident, Item,
+    ItemEnum, Token, Variant,
+};
+
+use crate::names::to_pascal_case;
+use crate::paths::{PathFinder, Rewriter};
+
+/// The arms listed in a ```#[fork(a, b)]``` attribute, if it is one.
+fn fork_arms(attr: &Attribute) -> Option<Vec<Ident>> {
+    if !attr.path.is_ident("fork") {
+        return None;
+    }
+
+    let arms = attr
+        .parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)
+        .unwrap_or_else(|error| {
+            panic!(
+                "Expected a list of arms such as #[fork(fast, safe)]: {} {:?}",
+                error,
+                attr.span()
+            )
+        });
+    Some(arms.into_iter().collect())
+}
+
+/// Collects the arms named in the ```#[fork(..)]``` attributes of an item, in order of appearance.
+#[derive(Default)]
+struct Arms(Vec<String>);
+
+impl Arms {
+    fn collect(&mut self, attrs: &[Attribute]) {
+        for arm in attrs.iter().filter_map(fork_arms).flatten() {
+            let arm = arm.to_string();
+            if !self.0.contains(&arm) {
+                self.0.push(arm);
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Arms {
+    fn visit_field(&mut self, field: &'ast Field) {
+        self.collect(&field.attrs);
+        visit::visit_field(self, field);
+    }
+
+    fn visit_variant(&mut self, variant: &'ast Variant) {
+        self.collect(&variant.attrs);
+        visit::visit_variant(self, variant);
+    }
+
+    fn visit_field_value(&mut self, field: &'ast FieldValue) {
+        self.collect(&field.attrs);
+        visit::visit_field_value(self, field);
+    }
+}
+
+/// Removes the fields, variants and field values that are not part of the chosen arm, along with
+/// all ```#[fork(..)]``` attributes.
+struct Selection<'a> {
+    arm: Option<&'a str>,
+}
+
+impl Selection<'_> {
+    /// Whether the element with the given attributes is kept, in which case its ```#[fork(..)]```s are removed.
+    fn keep(&self, attrs: &mut Vec<Attribute>) -> bool {
+        let mut arms = attrs.iter().filter_map(fork_arms).peekable();
+        let keep = arms.peek().is_none()
+            || arms
+                .flatten()
+                .any(|arm| matches!(self.arm, Some(chosen) if arm == chosen));
+        attrs.retain(|attr| !attr.path.is_ident("fork"));
+        keep
+    }
+
+    fn select<T, P: Default>(
+        &self,
+        elements: Punctuated<T, P>,
+        attrs: impl Fn(&mut T) -> &mut Vec<Attribute>,
+    ) -> Punctuated<T, P> {
+        elements
+            .into_iter()
+            .filter_map(|mut element| self.keep(attrs(&mut element)).then_some(element))
+            .collect()
+    }
+}
+
+impl VisitMut for Selection<'_> {
+    fn visit_fields_mut(&mut self, fields: &mut Fields) {
+        match fields {
+            Fields::Named(fields) => {
+                fields.named =
+                    self.select(std::mem::take(&mut fields.named), |field| &mut field.attrs)
+            }
+            Fields::Unnamed(fields) => {
+                fields.unnamed = self.select(std::mem::take(&mut fields.unnamed), |field| {
+                    &mut field.attrs
+                })
+            }
+            Fields::Unit => {}
+        }
+        visit_mut::visit_fields_mut(self, fields);
+    }
+
+    fn visit_item_enum_mut(&mut self, item: &mut ItemEnum) {
+        item.variants = self.select(std::mem::take(&mut item.variants), |variant| {
+            &mut variant.attrs
+        });
+        visit_mut::visit_item_enum_mut(self, item);
+    }
+
+    fn visit_expr_struct_mut(&mut self, expr: &mut ExprStruct) {
+        expr.fields = self.select(std::mem::take(&mut expr.fields), |field| &mut field.attrs);
+        visit_mut::visit_expr_struct_mut(self, expr);
+    }
+}
+
+/// The name of the item generated for the given path, e.g. ```Config_Fast``` or ```TABLE_FAST```.
+fn item_name(name: &Ident, path: &[String], constant: bool) -> Ident {
+    let prefix = name.to_string();
+    let prefix = prefix.trim_start_matches("r#");
+    let new_name = if constant {
+        format!("{}_{}", prefix, path.join("_").to_uppercase())
+    } else {
+        format!("{}_{}", prefix, to_pascal_case(&path.join("_")))
+    };
+    Ident::new(&new_name, name.span())
+}
+
+/// Builds one copy of a struct, enum, const or static item per path. The fields, variants and field
+/// values marked with ```#[fork(..)]``` form a fork point of their own, and are only kept for the
+/// arms that they list. Fork points in expressions (e.g. the initializer of a const) work as usual.
+pub(crate) fn items(original: &Item, max_paths: usize) -> TokenStream {
+    let mut arms = Arms::default();
+    arms.visit_item(original);
+    let seed = if arms.0.is_empty() {
+        vec![vec![]]
+    } else {
+        arms.0.iter().map(|arm| vec![arm.clone()]).collect()
+    };
+
+    let mut finder = PathFinder::new(seed, max_paths);
+    finder.visit_item(original);
+    let paths = finder.into_inner();
+
+    let mut tokens = TokenStream::new();
+    for path in paths {
+        let mut item = original.clone();
+        let (arm, rest) = if arms.0.is_empty() {
+            (None, path.as_slice())
+        } else {
+            (Some(path[0].as_str()), &path[1..])
+        };
+        Selection { arm }.visit_item_mut(&mut item);
+        Rewriter::new(rest.to_vec()).visit_item_mut(&mut item);
+
+        if !path.is_empty() {
+            let allow: Attribute = parse_quote!(#[allow(non_camel_case_types)]);
+            match &mut item {
+                Item::Struct(item) => {
+                    item.ident = item_name(&item.ident, &path, false);
+                    item.attrs.push(allow);
+                }
+                Item::Enum(item) => {
+                    item.ident = item_name(&item.ident, &path, false);
+                    item.attrs.push(allow);
+                }
+                Item::Const(item) => item.ident = item_name(&item.ident, &path, true),
+                Item::Static(item) => item.ident = item_name(&item.ident, &path, true),
+                _ => panic!(
+                    "#[crossroads] can only be used with functions, impl blocks, structs, enums, consts and statics. {:?}",
+                    original.span()
+                ),
+            }
+        }
+        item.to_tokens(&mut tokens);
+    }
+    tokens
+}
@@ -0,0 +1,310 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! The expansion logic behind the ```crossroads``` proc macro, usable without ```proc_macro```.
+//!
+//! This crate works on [proc_macro2::TokenStream]s and [syn] syntax trees, so other tools such as
+//! build scripts or linters can reuse the exact same expansion as the ```#[crossroads]``` attribute:
+//!
+//! ```rust
+//! use syn::parse_quote;
+//!
+//! let function: syn::ItemFn = parse_quote! {
+//!     fn empty() {
+//!         match fork!() {
+//!             by_default => {}
+//!             after_clear => {}
+//!         }
+//!     }
+//! };
+//!
+//! let paths = crossroads_core::enumerate_paths(&function);
+//! assert_eq!(2, paths.len());
+//! assert_eq!(&["after_clear"], paths[1].segments());
+//!
+//! let expanded = crossroads_core::expand(&function, &paths[1]);
+//! assert_eq!("empty_after_clear", expanded.sig.ident.to_string());
+//! ```
+//!
+//! See the documentation of the ```crossroads``` crate for the user-facing side of things.
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{Attribute, Ident, Item, ItemFn};
+
+use crate::bench::{criterion, divan};
+use crate::fork::{normalize, normalize_impl, signature_type_fork};
+use crate::generics::instantiate;
+use crate::hooks::{hooks, wrap};
+use crate::impls::impls;
+use crate::items::items;
+use crate::options::{Options, Output};
+use crate::output::{
+    allow_unused_inputs, dispatch, misplaced_test, registry, rerun, runner, trials,
+};
+use crate::paths::{PathFinder, Rewriter};
+use crate::proptest::proptest;
+use crate::sets::defer;
+pub use crate::sets::{fork_set, resolve_fork_set};
+use crate::snapshot::snapshot;
+pub use crate::test::test;
+pub use crate::typed::typed;
+
+mod bench;
+mod fork;
+mod generics;
+mod hooks;
+mod impls;
+mod items;
+mod names;
+mod options;
+mod output;
+mod paths;
+mod proptest;
+mod sets;
+mod snapshot;
+mod test;
+mod typed;
+
+/// A single path through a function, i.e. the sequence of arms chosen at its fork points.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Path {
+    segments: Vec<String>,
+}
+
+impl Path {
+    pub fn new(segments: Vec<String>) -> Self {
+        Self { segments }
+    }
+
+    /// The name segments contributed by each of the fork points along this path.
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// The name of the function that is generated for this path through the given function.
+    pub fn function_name(&self, function: &Ident) -> Ident {
+        let mut new_name = function.to_string();
+        for fork in &self.segments {
+            new_name.push('_');
+            new_name.push_str(fork)
+        }
+
+        Ident::new(&new_name, function.span())
+    }
+}
+
+/// Computes all paths through the fork points of the given function, in the order in which they
+/// appear in the function.
+///
+/// # Panics
+///
+/// If the fork points within the function are malformed, e.g. ```match fork!()``` arms with patterns other
+/// than identifiers or paths to enum variants. The message describes the problem along with its span.
+pub fn enumerate_paths(function: &ItemFn) -> Vec<Path> {
+    enumerate(function, usize::MAX)
+}
+
+fn enumerate(function: &ItemFn, max_paths: usize) -> Vec<Path> {
+    let function = normalize(function);
+    let mut paths = PathFinder::new(vec![vec![]], max_paths);
+    // Types may be forked within the signature as well, e.g. those of the arguments.
+    paths.visit_signature(&function.sig);
+    paths.visit_block(&function.block);
+
+    paths.into_inner().into_iter().map(Path::new).collect()
+}
+
+/// Creates the version of the function that follows the given path, i.e. with every fork point
+/// replaced by the chosen arm and the name extended accordingly.
+///
+/// # Panics
+///
+/// If the fork points within the function are malformed (see [enumerate_paths]), or if the path has not
+/// been computed by [enumerate_paths] for the same function.
+pub fn expand(function: &ItemFn, path: &Path) -> ItemFn {
+    rewrite(function, path).0
+}
+
+/// Like [expand], but also returns the attributes of the arms taken along the path.
+fn rewrite(function: &ItemFn, path: &Path) -> (ItemFn, Vec<Attribute>) {
+    let mut function = normalize(function);
+    function.sig.ident = path.function_name(&function.sig.ident);
+
+    let mut rewriter = Rewriter::new(path.segments.clone());
+    rewriter.visit_signature_mut(&mut function.sig);
+    rewriter.visit_block_mut(&mut function.block);
+    (function, rewriter.into_arm_attrs())
+}
+
+/// The full expansion of ```#[crossroads(args)] input```, as performed by the attribute macro.
+///
+/// Options that can not be used with the given item are reported as compile errors, just like unknown
+/// options.
+///
+/// # Panics
+///
+/// If the fork points within the item are malformed, e.g. ```match fork!()``` arms with patterns other than
+/// identifiers or paths to enum variants, or if there are more paths than ```max_paths``` allows. The
+/// message points to the offending code, so that it can be reported as-is from within a proc macro.
+pub fn crossroads(args: TokenStream, input: TokenStream) -> TokenStream {
+    let options: Options = match syn::parse2(args.clone()) {
+        Ok(options) => options,
+        Err(error) => return error.to_compile_error(),
+    };
+    let item: syn::Result<Item> = syn::parse2(input);
+    if let Some(tokens) = item.as_ref().ok().and_then(|item| defer(&args, item)) {
+        // Fork sets are defined elsewhere, so the expansion continues once they have been resolved.
+        return tokens;
+    }
+    item.and_then(|item| expand_item(&options, item))
+        .unwrap_or_else(|error| error.to_compile_error())
+}
+
+fn expand_item(options: &Options, item: Item) -> syn::Result<TokenStream> {
+    let function: ItemFn = match item {
+        Item::Fn(function) => normalize(&function),
+        Item::Impl(item) => {
+            if !options.only_max_paths() {
+                return Err(syn::Error::new(
+                    item.span(),
+                    "#[crossroads] does not support any options but max_paths for impl blocks.",
+                ));
+            }
+            return impls(&normalize_impl(&item), options.max_paths);
+        }
+        item @ (Item::Struct(_) | Item::Enum(_) | Item::Const(_) | Item::Static(_)) => {
+            if !options.only_max_paths() {
+                return Err(syn::Error::new(
+                    item.span(),
+                    "#[crossroads] does not support any options but max_paths for structs, enums, consts and statics.",
+                ));
+            }
+            return Ok(items(&item, options.max_paths));
+        }
+        item => {
+            return Err(syn::Error::new(
+                item.span(),
+                "#[crossroads] can only be used with functions, impl blocks, structs, enums, consts and statics.",
+            ))
+        }
+    };
+
+    if options.output == Output::Dispatch {
+        if let Some(ty) = signature_type_fork(&function.sig) {
+            return Err(syn::Error::new(
+                ty.span(),
+                "fork_type!() cannot be used within the signature with #[crossroads(enum)], as that keeps a single signature for all paths.",
+            ));
+        }
+    }
+    let instances = instantiate(&function, &options.types)?;
+    let mut tokens = TokenStream::new();
+    if matches!(
+        options.output,
+        Output::Rerun | Output::Process | Output::Snapshot
+    ) {
+        // These keep a single function per instantiation of the generic parameters.
+        for (segments, instance) in &instances {
+            let mut instance = instance.clone();
+            instance.sig.ident = Path::new(segments.clone()).function_name(&instance.sig.ident);
+            match options.output {
+                Output::Rerun => rerun(&instance, false)?.to_tokens(&mut tokens),
+                Output::Process => rerun(&instance, true)?.to_tokens(&mut tokens),
+                _ => snapshot(&instance)?.to_tokens(&mut tokens),
+            }
+        }
+        return Ok(tokens);
+    }
+
+    let mut paths = Vec::new();
+    let mut new_functions = Vec::new();
+    let mut arm_attrs = Vec::new();
+    for (segments, instance) in &instances {
+        for path in enumerate(instance, options.max_paths) {
+            let (mut new_function, attrs) = rewrite(instance, &path);
+            allow_unused_inputs(instance, &mut new_function);
+            let path = Path::new(segments.iter().cloned().chain(path.segments).collect());
+            new_function.sig.ident = path.function_name(&function.sig.ident);
+            if let Some(wrapper) = &options.wrap_with {
+                wrap(&mut new_function, wrapper)?;
+            }
+            hooks(
+                &mut new_function,
+                options.before.as_ref(),
+                options.after.as_ref(),
+                &path,
+            );
+            paths.push(path);
+            new_functions.push(new_function);
+            arm_attrs.push(attrs);
+        }
+    }
+    assert!(
+        paths.len() <= options.max_paths,
+        "Found more than {} paths through the function and the instantiations of its generic parameters, use #[crossroads(max_paths = ..)] to raise the limit. {:?}",
+        options.max_paths,
+        function.sig.span()
+    );
+
+    // Anything that refers to the generated functions as a whole is based on a non-generic version.
+    let function = &instances[0].1;
+    if options.all {
+        runner(function, &new_functions, options.output == Output::Dispatch)?
+            .to_tokens(&mut tokens);
+    }
+    if options.trials {
+        trials(function, &paths, &new_functions, &arm_attrs)?.to_tokens(&mut tokens);
+    }
+    if options.output == Output::Dispatch {
+        dispatch(function, &paths, &new_functions).to_tokens(&mut tokens);
+        return Ok(tokens);
+    }
+    if options.output == Output::Criterion {
+        return criterion(function, &paths, &new_functions);
+    }
+    if options.output == Output::Divan {
+        return divan(function, &paths, &new_functions);
+    }
+
+    if options.variants {
+        registry(function, &new_functions)?.to_tokens(&mut tokens);
+    }
+    if new_functions
+        .iter()
+        .all(|new_function| new_function.sig.ident != function.sig.ident)
+    {
+        if let Some(placeholder) = misplaced_test(function) {
+            placeholder.to_tokens(&mut tokens);
+        }
+    }
+    for function in new_functions {
+        match &options.proptest {
+            Some(config) => proptest(&function, config)?.to_tokens(&mut tokens),
+            None => function.to_tokens(&mut tokens),
+        }
+    }
+    Ok(tokens)
+}
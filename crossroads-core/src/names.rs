@@ -0,0 +1,105 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{Ident, Type};
+
+/// Converts an identifier such as ```AfterAdd``` into ```after_add```, so that enum variants
+/// blend in with the remainder of the generated function names.
+pub(crate) fn to_snake_case(ident: &Ident) -> String {
+    let ident = ident.to_string();
+    let chars: Vec<char> = ident.chars().collect();
+    let mut snake = String::with_capacity(ident.len());
+    for (index, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let after_lower =
+                index > 0 && !chars[index - 1].is_uppercase() && chars[index - 1] != '_';
+            let before_lower = index > 0
+                && chars[index - 1].is_uppercase()
+                && matches!(chars.get(index + 1), Some(next) if next.is_lowercase());
+            if after_lower || before_lower {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Converts a name such as ```after_add``` into ```AfterAdd```, which is used for generated types.
+pub(crate) fn to_pascal_case(name: &str) -> String {
+    name.trim_start_matches("r#")
+        .split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .into_iter()
+                .flat_map(char::to_uppercase)
+                .chain(chars)
+        })
+        .collect()
+}
+
+/// The segment for a concrete type, made up of the identifiers within it, i.e. ```Vec<String>``` turns
+/// into ```vec_string```.
+pub(crate) fn type_segment(ty: &Type) -> String {
+    fn idents(tokens: TokenStream, into: &mut Vec<String>) {
+        let mut lifetime = false;
+        for token in tokens {
+            let after_apostrophe = lifetime;
+            lifetime = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '\'');
+            match token {
+                // Lifetimes such as 'static and placeholders are not a part of the name.
+                TokenTree::Ident(_) if after_apostrophe => {}
+                TokenTree::Ident(ident) if ident == "_" => {}
+                TokenTree::Ident(ident) => into.push(to_snake_case(&ident)),
+                TokenTree::Group(group) => idents(group.stream(), into),
+                TokenTree::Punct(_) | TokenTree::Literal(_) => {}
+            }
+        }
+    }
+
+    let mut segments = Vec::new();
+    idents(ty.to_token_stream(), &mut segments);
+    segments.join("_")
+}
+
+pub(crate) fn registry_name(name: &Ident) -> Ident {
+    Ident::new(
+        &format!(
+            "{}_VARIANTS",
+            name.to_string().trim_start_matches("r#").to_uppercase()
+        ),
+        name.span(),
+    )
+}
+
+pub(crate) fn enum_name(name: &Ident) -> Ident {
+    Ident::new(
+        &format!("{}Path", to_pascal_case(&name.to_string())),
+        name.span(),
+    )
+}
@@ -0,0 +1,314 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{bracketed, parenthesized, token, Expr, ExprPath, Ident, LitInt, Path, Token, Type};
+
+/// What the attribute turns the function into.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Output {
+    /// One function per path.
+    #[default]
+    Functions,
+    /// A single function that selects the path through an additional ```<Name>Path``` parameter.
+    Dispatch,
+    /// A single function that re-runs its body until every path has been visited.
+    Rerun,
+    /// Like ```Rerun```, but takes every arm of a fork point in a child process of its own.
+    Process,
+    /// A single function that runs everything before a fork point once and continues each arm
+    /// with its own clone of the local variables.
+    Snapshot,
+    /// A single function that registers a ```criterion``` benchmark per path.
+    Criterion,
+    /// A module named after the function with a ```divan``` benchmark per path.
+    Divan,
+}
+
+impl Output {
+    /// The option that selects this output, as written within ```#[crossroads(..)]```.
+    pub(crate) fn option(&self) -> &'static str {
+        match self {
+            Output::Functions => "default",
+            Output::Dispatch => "enum",
+            Output::Rerun => "runtime",
+            Output::Process => "process",
+            Output::Snapshot => "clone",
+            Output::Criterion => "bench = criterion",
+            Output::Divan => "bench = divan",
+        }
+    }
+}
+
+/// The number of paths up to which functions are generated, unless configured otherwise. There is no limit by
+/// default, so that functions which expanded before ```max_paths``` existed keep doing so.
+pub(crate) const DEFAULT_MAX_PATHS: usize = usize::MAX;
+
+/// The arguments that can be given to the attribute, e.g. ```#[crossroads(enum, all)]```.
+pub(crate) struct Options {
+    pub(crate) output: Output,
+    /// Generate an additional ```<name>_all``` function that runs all paths and reports every failure.
+    pub(crate) all: bool,
+    /// Generate an additional ```<name>_trials``` function that lists every path as a ```libtest_mimic::Trial```.
+    pub(crate) trials: bool,
+    /// Generate an additional ```<NAME>_VARIANTS``` constant that lists every generated function along with its name.
+    pub(crate) variants: bool,
+    /// Fail instead of generating functions for more than this many paths.
+    pub(crate) max_paths: usize,
+    /// The concrete types to instantiate each generic type parameter with, e.g. ```types(T = [u8, u64])```.
+    pub(crate) types: Vec<Instantiation>,
+    /// Called with the path at the start of every generated function, e.g. ```before = setup```.
+    pub(crate) before: Option<ExprPath>,
+    /// Called with the path once every generated function is done, even if it panics.
+    pub(crate) after: Option<ExprPath>,
+    /// Wraps the body of every generated function, e.g. ```wrap_with = loom::model```.
+    pub(crate) wrap_with: Option<Wrapper>,
+    /// Turn every generated function into a property test, e.g. ```proptest(Config::with_cases(64))```.
+    pub(crate) proptest: Option<Proptest>,
+}
+
+/// The configuration of the ```proptest!``` blocks generated for the paths.
+pub(crate) struct Proptest {
+    pub(crate) config: Option<Expr>,
+}
+
+/// What the body of a generated function is handed to as a closure.
+pub(crate) enum Wrapper {
+    /// A function call, i.e. ```wrapper(|| { .. })```.
+    Function(ExprPath),
+    /// A macro invocation, i.e. ```wrapper!(|| { .. })```.
+    Macro(Path),
+}
+
+impl Parse for Wrapper {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path: ExprPath = input.parse()?;
+        if input.peek(Token![!]) {
+            input.parse::<Token![!]>()?;
+            Ok(Self::Macro(path.path))
+        } else {
+            Ok(Self::Function(path))
+        }
+    }
+}
+
+/// The concrete types to instantiate a single generic type parameter with.
+pub(crate) struct Instantiation {
+    pub(crate) param: Ident,
+    pub(crate) types: Vec<Type>,
+}
+
+impl Parse for Instantiation {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let param: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let content;
+        bracketed!(content in input);
+        let types: Punctuated<Type, Token![,]> = content.parse_terminated(Type::parse)?;
+        if types.is_empty() {
+            return Err(syn::Error::new(
+                param.span(),
+                format!("Must list at least one type for `{}`.", param),
+            ));
+        }
+        Ok(Self {
+            param,
+            types: types.into_iter().collect(),
+        })
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            output: Output::default(),
+            all: false,
+            trials: false,
+            variants: false,
+            max_paths: DEFAULT_MAX_PATHS,
+            types: Vec::new(),
+            before: None,
+            after: None,
+            wrap_with: None,
+            proptest: None,
+        }
+    }
+}
+
+impl Options {
+    /// Whether no option other than ```max_paths``` has been given.
+    pub(crate) fn only_max_paths(&self) -> bool {
+        self.output == Output::Functions
+            && !self.all
+            && !self.trials
+            && !self.variants
+            && self.types.is_empty()
+            && self.before.is_none()
+            && self.after.is_none()
+            && self.wrap_with.is_none()
+            && self.proptest.is_none()
+    }
+
+    fn set_output(&mut self, key: &Ident, output: Output) -> syn::Result<()> {
+        if self.output != Output::Functions {
+            return Err(syn::Error::new(
+                key.span(),
+                format!(
+                    "`{}` cannot be combined with `{}`.",
+                    key,
+                    self.output.option()
+                ),
+            ));
+        }
+        self.output = output;
+        Ok(())
+    }
+}
+
+impl Parse for Options {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut options = Self::default();
+        while !input.is_empty() {
+            let key = Ident::parse_any(input)?;
+            match key.to_string().as_str() {
+                "enum" => options.set_output(&key, Output::Dispatch)?,
+                "runtime" => options.set_output(&key, Output::Rerun)?,
+                "process" => options.set_output(&key, Output::Process)?,
+                "clone" => options.set_output(&key, Output::Snapshot)?,
+                "bench" => {
+                    input.parse::<Token![=]>()?;
+                    let harness: Ident = input.parse()?;
+                    match harness.to_string().as_str() {
+                        "criterion" => options.set_output(&key, Output::Criterion)?,
+                        "divan" => options.set_output(&key, Output::Divan)?,
+                        _ => {
+                            return Err(syn::Error::new(
+                                harness.span(),
+                                format!(
+                                "Unknown benchmark harness `{}`, expected `criterion` or `divan`.",
+                                harness
+                            ),
+                            ))
+                        }
+                    }
+                }
+                "all" => options.all = true,
+                "trials" => options.trials = true,
+                "variants" => options.variants = true,
+                "max_paths" => {
+                    input.parse::<Token![=]>()?;
+                    options.max_paths = input.parse::<LitInt>()?.base10_parse()?;
+                }
+                "before" => {
+                    input.parse::<Token![=]>()?;
+                    options.before = Some(input.parse()?);
+                }
+                "after" => {
+                    input.parse::<Token![=]>()?;
+                    options.after = Some(input.parse()?);
+                }
+                "wrap_with" => {
+                    input.parse::<Token![=]>()?;
+                    options.wrap_with = Some(input.parse()?);
+                }
+                "proptest" => {
+                    let config = if input.peek(token::Paren) {
+                        let content;
+                        parenthesized!(content in input);
+                        Some(content.parse()?)
+                    } else {
+                        None
+                    };
+                    options.proptest = Some(Proptest { config });
+                }
+                "types" => {
+                    let content;
+                    parenthesized!(content in input);
+                    let types: Punctuated<Instantiation, Token![,]> =
+                        content.parse_terminated(Instantiation::parse)?;
+                    options.types.extend(types);
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("Unknown option `{}` for #[crossroads].", key),
+                    ))
+                }
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        if options.all && !matches!(options.output, Output::Functions | Output::Dispatch) {
+            return Err(syn::Error::new(
+                input.span(),
+                format!(
+                    "`all` cannot be combined with `{}`.",
+                    options.output.option()
+                ),
+            ));
+        }
+        if options.trials && options.output != Output::Functions {
+            return Err(syn::Error::new(
+                input.span(),
+                format!(
+                    "`trials` cannot be combined with `{}`.",
+                    options.output.option()
+                ),
+            ));
+        }
+        if options.variants && options.output != Output::Functions {
+            return Err(syn::Error::new(
+                input.span(),
+                format!(
+                    "`variants` cannot be combined with `{}`.",
+                    options.output.option()
+                ),
+            ));
+        }
+        if (options.before.is_some()
+            || options.after.is_some()
+            || options.wrap_with.is_some()
+            || options.proptest.is_some())
+            && options.output != Output::Functions
+        {
+            return Err(syn::Error::new(
+                input.span(),
+                format!(
+                    "`before`, `after`, `wrap_with` and `proptest` cannot be combined with `{}`.",
+                    options.output.option()
+                ),
+            ));
+        }
+        if !options.types.is_empty() && options.output == Output::Dispatch {
+            return Err(syn::Error::new(
+                input.span(),
+                "`types` cannot be combined with `enum`.",
+            ));
+        }
+        Ok(options)
+    }
+}
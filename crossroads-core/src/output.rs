@@ -0,0 +1,511 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::{
+    parse_quote, Attribute, Expr, FnArg, Ident, ItemConst, ItemEnum, ItemFn, ItemImpl, Pat,
+    ReturnType, Signature, Type,
+};
+
+use crate::fork::reject_type_forks;
+use crate::names::{enum_name, registry_name, to_pascal_case};
+use crate::paths::TrackerRewriter;
+use crate::Path;
+
+/// Builds the ```<Name>Path``` enum with one variant per path, along with a single function that takes
+/// it as an additional first argument and runs the corresponding path.
+pub(crate) fn dispatch(original: &ItemFn, paths: &[Path], functions: &[ItemFn]) -> TokenStream {
+    let name = &original.sig.ident;
+    let span = name.span();
+    let enum_name = enum_name(name);
+    let variants: Vec<Ident> = paths
+        .iter()
+        .map(|path| {
+            let variant = if path.segments().is_empty() {
+                to_pascal_case(&name.to_string())
+            } else {
+                to_pascal_case(&path.segments().join("_"))
+            };
+            Ident::new(&variant, span)
+        })
+        .collect();
+    let names = functions
+        .iter()
+        .map(|function| function.sig.ident.to_string());
+    let blocks = functions.iter().map(|function| &function.block);
+
+    let vis = &original.vis;
+    let item_enum: ItemEnum = parse_quote! {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        #vis enum #enum_name {
+            #(#variants),*
+        }
+    };
+
+    let parameter = Ident::new("crossroads_path", span);
+    let mut function = original.clone();
+    function
+        .sig
+        .inputs
+        .insert(0, parse_quote!(#parameter: #enum_name));
+    *function.block = parse_quote!({
+        match #parameter {
+            #(#enum_name::#variants => #blocks)*
+        }
+    });
+
+    let mut run = original.sig.clone();
+    run.ident = Ident::new("run", span);
+    let mut arguments = Vec::with_capacity(run.inputs.len());
+    for (index, input) in run.inputs.iter_mut().enumerate() {
+        if let FnArg::Typed(input) = input {
+            let argument = Ident::new(&format!("argument_{}", index), span);
+            input.attrs.clear();
+            *input.pat = parse_quote!(#argument);
+            arguments.push(argument);
+        }
+    }
+    run.inputs.insert(0, parse_quote!(self));
+
+    let mut call: Expr = parse_quote!(#name(self, #(#arguments),*));
+    if run.asyncness.is_some() {
+        call = parse_quote!(#call.await);
+    }
+    if run.unsafety.is_some() {
+        call = parse_quote!(unsafe { #call });
+    }
+
+    let all_doc = format!(
+        "All paths through ```{}```, in the order in which they appear in the function.",
+        name
+    );
+    let run_doc = format!("Runs ```{}``` along this path.", name);
+    let item_impl: ItemImpl = parse_quote! {
+        #[allow(dead_code)]
+        impl #enum_name {
+            #[doc = #all_doc]
+            pub const ALL: &'static [Self] = &[#(Self::#variants),*];
+
+            /// The name of the function that would have been generated for this path.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(Self::#variants => #names),*
+                }
+            }
+
+            #[doc = #run_doc]
+            pub #run {
+                #call
+            }
+        }
+    };
+
+    let from_str: ItemImpl = parse_quote! {
+        impl ::core::str::FromStr for #enum_name {
+            type Err = ::std::string::String;
+
+            fn from_str(name: &str) -> ::core::result::Result<Self, Self::Err> {
+                Self::ALL
+                    .iter()
+                    .copied()
+                    .find(|path| path.name() == name)
+                    .ok_or_else(|| ::std::format!("Unknown path `{}` through `{}`.", name, stringify!(#name)))
+            }
+        }
+    };
+
+    let mut tokens = TokenStream::new();
+    item_enum.to_tokens(&mut tokens);
+    item_impl.to_tokens(&mut tokens);
+    from_str.to_tokens(&mut tokens);
+    function.to_tokens(&mut tokens);
+    tokens
+}
+
+/// Whether the generated functions can be turned into plain function pointers, i.e. whether they
+/// do not take any arguments, are not generic and are not ```async```.
+pub(crate) fn is_plain(sig: &Signature) -> bool {
+    sig.inputs.is_empty()
+        && sig.generics.params.is_empty()
+        && sig.asyncness.is_none()
+        && sig.variadic.is_none()
+        && !matches!(&sig.output, ReturnType::Type(_, ty) if matches!(ty.as_ref(), Type::ImplTrait(_)))
+}
+
+/// Whether the function is certainly associated with a type, i.e. placed within an impl block.
+pub(crate) fn is_associated(sig: &Signature) -> bool {
+    sig.receiver().is_some() || mentions(sig.to_token_stream(), &Ident::new("Self", sig.span()))
+}
+
+/// Whether the function returns a ```Result```, judging by the name of its return type.
+fn returns_result(output: &ReturnType) -> bool {
+    match output {
+        ReturnType::Type(_, ty) => matches!(
+            ty.as_ref(),
+            Type::Path(ty) if ty.qself.is_none()
+                && matches!(ty.path.segments.last(), Some(segment) if segment.ident == "Result")
+        ),
+        ReturnType::Default => false,
+    }
+}
+
+/// Turns the given call of a generated function into a ```Result<(), String>```, with the error of functions
+/// returning a ```Result``` formatted the same way as by the test harness. Other return values are discarded.
+fn outcome(output: &ReturnType, call: Expr) -> Expr {
+    if returns_result(output) {
+        parse_quote!(#call.map(|_| ()).map_err(|error| ::std::format!("Error: {:?}", error)))
+    } else {
+        parse_quote!({
+            #call;
+            ::core::result::Result::Ok(())
+        })
+    }
+}
+
+/// The ```#[cfg(..)]``` attributes that items referring to the generated functions need to carry.
+fn cfgs(original: &ItemFn) -> Vec<Attribute> {
+    // Functions marked with #[test] only exist in test builds, so anything referring to them must not either.
+    original
+        .attrs
+        .iter()
+        .filter_map(|attr| -> Option<Attribute> {
+            if attr.path.is_ident("cfg") {
+                Some(attr.clone())
+            } else if attr.path.is_ident("test") {
+                Some(parse_quote!(#[cfg(test)]))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Builds the ```<NAME>_VARIANTS``` constant, which lists every generated function together with its name.
+/// This is only possible for functions that can be turned into plain function pointers, see [is_plain].
+pub(crate) fn registry(original: &ItemFn, functions: &[ItemFn]) -> syn::Result<ItemConst> {
+    let sig = &original.sig;
+    // Within impl blocks, the generated functions could only be referred to through Self.
+    if !is_plain(sig) || is_associated(sig) {
+        return Err(syn::Error::new(
+            sig.span(),
+            "#[crossroads(variants)] can only be used with free functions without arguments, generic parameters or async.",
+        ));
+    }
+    // With generic parameters instantiated with different types, the return types may differ as well.
+    let output = sig.output.to_token_stream().to_string();
+    if let Some(function) = functions
+        .iter()
+        .find(|function| function.sig.output.to_token_stream().to_string() != output)
+    {
+        return Err(syn::Error::new(
+            sig.span(),
+            format!(
+                "#[crossroads(variants)] requires every generated function to return the same type, but `{}` does not.",
+                function.sig.ident
+            ),
+        ));
+    }
+
+    let cfgs = cfgs(original);
+    let vis = &original.vis;
+    let unsafety = &sig.unsafety;
+    let abi = &sig.abi;
+    let output = &sig.output;
+    let name = registry_name(&sig.ident);
+    let doc = format!(
+        "The functions generated for every path through ```{}```, along with their names.",
+        sig.ident
+    );
+    let names = functions
+        .iter()
+        .map(|function| function.sig.ident.to_string());
+    let functions = functions.iter().map(|function| &function.sig.ident);
+
+    Ok(parse_quote! {
+        #(#cfgs)*
+        #[doc = #doc]
+        #[allow(dead_code)]
+        #vis const #name: &[(&str, #unsafety #abi fn() #output)] = &[#((#names, #functions)),*];
+    })
+}
+
+/// Whether the ident appears anywhere within the tokens, including the arguments of macros.
+pub(crate) fn mentions(tokens: TokenStream, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(candidate) => candidate == *ident,
+        TokenTree::Group(group) => mentions(group.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Allows the arguments of a generated function to go unused if only the arms that are not taken along
+/// its path used them, as is common for fixtures that are injected into a test.
+pub(crate) fn allow_unused_inputs(original: &ItemFn, function: &mut ItemFn) {
+    for input in &mut function.sig.inputs {
+        if let FnArg::Typed(input) = input {
+            if let Pat::Ident(pat) = input.pat.as_ref() {
+                let body = function.block.to_token_stream();
+                if mentions(original.block.to_token_stream(), &pat.ident)
+                    && !mentions(body, &pat.ident)
+                {
+                    input.attrs.push(parse_quote!(#[allow(unused_variables)]));
+                }
+            }
+        }
+    }
+}
+
+/// Stands in for the original function in test builds, as ```#[test]``` refers to it by name if it has been placed
+/// above ```#[crossroads]``` (and therefore only applies to the original function). The return type does not
+/// implement ```Termination``` and the deprecation note names the actual problem, without any items next to the
+/// function (which might be placed in an impl block). Associated functions can not be tests, so nothing is emitted
+/// for those that are recognizable as such.
+pub(crate) fn misplaced_test(original: &ItemFn) -> Option<ItemFn> {
+    if is_associated(&original.sig) {
+        return None;
+    }
+
+    let ident = &original.sig.ident;
+    Some(parse_quote! {
+        #[cfg(test)]
+        #[doc(hidden)]
+        #[allow(dead_code)]
+        #[deprecated(note = "#[test] must be placed below #[crossroads], as it only applies to the original function otherwise")]
+        fn #ident() -> impl ::core::marker::Sized {
+            struct Misplaced;
+            Misplaced
+        }
+    })
+}
+
+/// Builds the ```<name>_all``` function, which runs every path (catching panics along the way) and
+/// reports all paths that failed at once. Paths returning an ```Err``` count as failed as well.
+pub(crate) fn runner(
+    original: &ItemFn,
+    functions: &[ItemFn],
+    dispatch: bool,
+) -> syn::Result<ItemFn> {
+    let sig = &original.sig;
+    if !is_plain(sig) || sig.unsafety.is_some() {
+        return Err(syn::Error::new(
+            sig.span(),
+            "#[crossroads(all)] can only be used with safe functions without arguments, generic parameters or async.",
+        ));
+    }
+
+    let cfgs = cfgs(original);
+    let vis = &original.vis;
+    let name = &sig.ident;
+    let runner_name = Ident::new(
+        &format!("{}_all", name.to_string().trim_start_matches("r#")),
+        name.span(),
+    );
+    let variants: Expr = if dispatch {
+        let enum_name = enum_name(name);
+        let outcome = outcome(&sig.output, parse_quote!(path.run()));
+        parse_quote!(#enum_name::ALL.iter().map(|path| (path.name(), move || #outcome)))
+    } else {
+        // The instantiations of generic parameters may return different types, so every function is wrapped
+        // on its own.
+        let names = functions
+            .iter()
+            .map(|function| function.sig.ident.to_string());
+        let outcomes = functions.iter().map(|function| {
+            let ident = &function.sig.ident;
+            outcome(&function.sig.output, parse_quote!(#ident()))
+        });
+        parse_quote! {
+            [#((#names, (|| #outcomes) as fn() -> ::core::result::Result<(), ::std::string::String>)),*]
+        }
+    };
+
+    Ok(parse_quote! {
+        #(#cfgs)*
+        #[allow(dead_code)]
+        #vis fn #runner_name() {
+            let mut total = 0;
+            let mut failures = ::std::vec::Vec::new();
+            for (name, function) in #variants {
+                total += 1;
+                let message = match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(function)) {
+                    ::core::result::Result::Ok(::core::result::Result::Ok(())) => continue,
+                    ::core::result::Result::Ok(::core::result::Result::Err(message)) => message,
+                    ::core::result::Result::Err(payload) => {
+                        if let ::core::option::Option::Some(message) = payload.downcast_ref::<&str>() {
+                            message.to_string()
+                        } else if let ::core::option::Option::Some(message) = payload.downcast_ref::<::std::string::String>() {
+                            message.clone()
+                        } else {
+                            ::std::string::String::from("Box<dyn Any>")
+                        }
+                    }
+                };
+                failures.push((name, message));
+            }
+
+            if !failures.is_empty() {
+                let width = failures.iter().map(|(name, _)| name.len()).max().unwrap_or_default();
+                let mut summary = ::std::format!(
+                    "{} of {} paths through `{}` failed:\n",
+                    failures.len(),
+                    total,
+                    stringify!(#name)
+                );
+                for (name, message) in &failures {
+                    let message = message.replace('\n', &::std::format!("\n  {:width$} | ", "", width = width));
+                    summary.push_str(&::std::format!("  {:width$} | {}\n", name, message, width = width));
+                }
+                ::std::panic!("{}", summary);
+            }
+        }
+    })
+}
+
+/// Builds the ```<name>_trials``` function, which lists every generated function as a ```libtest_mimic::Trial```
+/// named after its path (```empty::after_add::and_clear```). Paths through arms marked with ```#[ignore]```
+/// are ignored, and the ```#[kind(..)]```s of the arms along a path are combined into the kind of its trial.
+pub(crate) fn trials(
+    original: &ItemFn,
+    paths: &[Path],
+    functions: &[ItemFn],
+    arm_attrs: &[Vec<Attribute>],
+) -> syn::Result<ItemFn> {
+    let sig = &original.sig;
+    if !is_plain(sig) || sig.unsafety.is_some() {
+        return Err(syn::Error::new(
+            sig.span(),
+            "#[crossroads(trials)] can only be used with safe functions without arguments, generic parameters or async.",
+        ));
+    }
+    let returns_unit = match &sig.output {
+        ReturnType::Default => true,
+        ReturnType::Type(_, ty) => {
+            matches!(ty.as_ref(), Type::Tuple(tuple) if tuple.elems.is_empty())
+        }
+    };
+    if !returns_unit && !returns_result(&sig.output) {
+        return Err(syn::Error::new(
+            sig.output.span(),
+            "#[crossroads(trials)] can only be used with functions that return nothing or a Result.",
+        ));
+    }
+
+    let cfgs = cfgs(original);
+    let vis = &original.vis;
+    let name = sig.ident.to_string();
+    let name = name.trim_start_matches("r#");
+    let trials_name = Ident::new(&format!("{}_trials", name), sig.ident.span());
+    let trials =
+        paths
+            .iter()
+            .zip(functions)
+            .zip(arm_attrs)
+            .map(|((path, function), attrs)| -> Expr {
+                let trial_name = std::iter::once(name)
+                    .chain(path.segments().iter().map(String::as_str))
+                    .collect::<Vec<&str>>()
+                    .join("::");
+                let ignored = attrs.iter().any(|attr| attr.path.is_ident("ignore"));
+                let kind = attrs
+                    .iter()
+                    .filter(|attr| attr.path.is_ident("kind"))
+                    .map(|attr| {
+                        let kind: Ident = attr.parse_args().unwrap_or_else(|error| {
+                            panic!(
+                                "Expected a name within #[kind(..)]: {} {:?}",
+                                error,
+                                attr.span()
+                            )
+                        });
+                        kind.to_string()
+                    })
+                    .collect::<Vec<String>>()
+                    .join(",");
+
+                let function = &function.sig.ident;
+                let run: Expr = if returns_unit {
+                    parse_quote!({
+                        #function();
+                        ::core::result::Result::Ok(())
+                    })
+                } else {
+                    let outcome = outcome(&sig.output, parse_quote!(#function()));
+                    parse_quote!(#outcome.map_err(::libtest_mimic::Failed::from))
+                };
+                parse_quote! {
+                    ::libtest_mimic::Trial::test(#trial_name, || #run)
+                        .with_kind(#kind)
+                        .with_ignored_flag(#ignored)
+                }
+            });
+
+    Ok(parse_quote! {
+        #(#cfgs)*
+        #[allow(dead_code)]
+        #vis fn #trials_name() -> ::std::vec::Vec<::libtest_mimic::Trial> {
+            ::std::vec![#(#trials),*]
+        }
+    })
+}
+
+/// Builds a single function that runs its body once per path, with the fork points consulting a
+/// ```crossroads_runtime::Tracker``` to decide which arm to take in each run. With ```processes```,
+/// each arm is taken in a child process instead, see ```crossroads_runtime::run_in_processes```.
+pub(crate) fn rerun(original: &ItemFn, processes: bool) -> syn::Result<ItemFn> {
+    let sig = &original.sig;
+    let option = if processes { "process" } else { "runtime" };
+    if !sig.inputs.is_empty() || sig.asyncness.is_some() {
+        return Err(syn::Error::new(
+            sig.span(),
+            format!(
+                "#[crossroads({})] can only be used with functions without arguments that are not async.",
+                option
+            ),
+        ));
+    }
+    reject_type_forks(original, option);
+
+    let tracker = Ident::new("crossroads_tracker", sig.ident.span());
+    let mut block = original.block.clone();
+    TrackerRewriter::new(tracker.clone()).visit_block_mut(&mut block);
+
+    let name = sig.ident.to_string();
+    let output = match &sig.output {
+        ReturnType::Default => parse_quote!(()),
+        ReturnType::Type(_, ty) => ty.as_ref().clone(),
+    };
+
+    let runner = Ident::new(
+        if processes { "run_in_processes" } else { "run" },
+        sig.ident.span(),
+    );
+
+    let mut function = original.clone();
+    function.sig.output = ReturnType::Default;
+    *function.block = parse_quote!({
+        ::crossroads_runtime::#runner(#name, |#tracker: &mut ::crossroads_runtime::Tracker| -> #output #block);
+    });
+    Ok(function)
+}
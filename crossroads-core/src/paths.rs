@@ -0,0 +1,202 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::collections::VecDeque;
+
+use proc_macro2::Literal;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{parse_quote, visit, visit_mut, Attribute, Expr, Ident, Type};
+
+use crate::fork::{alternatives, arm_segment, type_alternatives, Alternative};
+
+pub(crate) type Paths<T> = Vec<Vec<T>>;
+
+pub(crate) struct PathFinder {
+    paths: Paths<String>,
+    /// The number of paths after which the search is aborted.
+    limit: usize,
+}
+
+impl PathFinder {
+    pub(crate) fn new(paths: Paths<String>, limit: usize) -> Self {
+        Self { paths, limit }
+    }
+
+    pub(crate) fn into_inner(self) -> Paths<String> {
+        self.paths
+    }
+}
+
+impl PathFinder {
+    /// Continues every path found so far with each of the alternatives of a fork point.
+    fn fork<T: Spanned>(
+        &mut self,
+        fork: &T,
+        alternatives: Vec<Alternative<T>>,
+        visit: impl Fn(&mut PathFinder, &T),
+    ) {
+        let mut new_paths = Paths::default();
+        for alternative in alternatives {
+            let mut this_paths = self.paths.clone();
+            for path in &mut this_paths {
+                path.push(alternative.segment.clone());
+            }
+
+            let mut this_pathfinder = PathFinder::new(this_paths, self.limit);
+            visit(&mut this_pathfinder, &alternative.replacement);
+
+            new_paths.append(&mut this_pathfinder.into_inner());
+            assert!(
+                new_paths.len() <= self.limit,
+                "Found more than {} paths through the function, use #[crossroads(max_paths = ..)] to raise the limit. {:?}",
+                self.limit,
+                fork.span()
+            );
+        }
+
+        self.paths = new_paths;
+    }
+}
+
+impl<'ast> Visit<'ast> for PathFinder {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let Some(alternatives) = alternatives(expr) {
+            self.fork(expr, alternatives, |finder, expr| finder.visit_expr(expr));
+        } else {
+            visit::visit_expr(self, expr);
+        }
+    }
+
+    fn visit_type(&mut self, ty: &'ast Type) {
+        if let Some(alternatives) = type_alternatives(ty) {
+            self.fork(ty, alternatives, |finder, ty| finder.visit_type(ty));
+        } else {
+            visit::visit_type(self, ty);
+        }
+    }
+}
+
+pub(crate) struct Rewriter {
+    along_path: VecDeque<String>,
+    /// The attributes of the ```match fork!()``` arms taken along the path, e.g. ```#[ignore]```.
+    arm_attrs: Vec<Attribute>,
+}
+
+impl Rewriter {
+    pub(crate) fn new(path: impl Into<VecDeque<String>>) -> Self {
+        Self {
+            along_path: path.into(),
+            arm_attrs: Vec::new(),
+        }
+    }
+
+    pub(crate) fn into_arm_attrs(self) -> Vec<Attribute> {
+        self.arm_attrs
+    }
+}
+
+impl Rewriter {
+    /// The replacement for the fork point along the path.
+    fn choose<T: Spanned>(&mut self, fork: &T, alternatives: Vec<Alternative<T>>) -> T {
+        let current = self
+            .along_path
+            .pop_front()
+            .expect("There should always be enough identifiers in this list.");
+
+        alternatives
+            .into_iter()
+            .find(|alternative| alternative.segment == current)
+            .unwrap_or_else(|| panic!("Did not find identifier {} in corresponding fork point. This is almost certainly a bug, please feel free to report it. {:?}", current, fork.span()))
+            .replacement
+    }
+}
+
+impl VisitMut for Rewriter {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Some(alternatives) = type_alternatives(ty) {
+            *ty = self.choose(ty, alternatives);
+            self.visit_type_mut(ty);
+        } else {
+            visit_mut::visit_type_mut(self, ty);
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Some(alternatives) = alternatives(expr) {
+            if let (Expr::Match(mtch), Some(current)) = (&*expr, self.along_path.front()) {
+                if let Some(arm) = mtch
+                    .arms
+                    .iter()
+                    .find(|arm| arm_segment(&arm.pat) == *current)
+                {
+                    self.arm_attrs.extend(arm.attrs.iter().cloned());
+                }
+            }
+            let mut replacement = self.choose(expr, alternatives);
+
+            std::mem::swap(expr, &mut replacement);
+            // This is kind of mean: If the expression that we are putting in place of the match is itself another match,
+            // it gets skipped here (as the recursive method assumes you have already visited the node that you give).
+            // As such, we need to manually recurse in this specific case.
+            self.visit_expr_mut(expr);
+        } else {
+            visit_mut::visit_expr_mut(self, expr);
+        }
+    }
+}
+
+/// Replaces every fork point with a ```match``` on the choice made by the runtime tracker, so that a
+/// single function body can visit all paths one after the other.
+pub(crate) struct TrackerRewriter {
+    tracker: Ident,
+}
+
+impl TrackerRewriter {
+    pub(crate) fn new(tracker: Ident) -> Self {
+        Self { tracker }
+    }
+}
+
+impl VisitMut for TrackerRewriter {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Some(alternatives) = alternatives(expr) {
+            let tracker = &self.tracker;
+            let segments = alternatives.iter().map(|alternative| &alternative.segment);
+            let indices = (0..alternatives.len()).map(Literal::usize_unsuffixed);
+            let replacements = alternatives
+                .iter()
+                .map(|alternative| &alternative.replacement);
+
+            *expr = parse_quote! {
+                match #tracker.fork(&[#(#segments),*]) {
+                    #(#indices => #replacements,)*
+                    _ => ::core::unreachable!(),
+                }
+            };
+        }
+
+        // The arms of the new match contain the replacements, which may contain fork points themselves.
+        visit_mut::visit_expr_mut(self, expr);
+    }
+}
@@ -0,0 +1,103 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::spanned::Spanned;
+use syn::{parse_quote, Expr, FnArg, ItemFn, Pat, ReturnType};
+
+use crate::options::Proptest;
+use crate::output::mentions;
+
+/// Wraps a generated function in a ```proptest!``` block of its own. Its arguments become the inputs of the
+/// property, either drawn from the strategy given with ```#[strategy(..)]``` or from ```any::<T>()```.
+pub(crate) fn proptest(function: &ItemFn, proptest: &Proptest) -> syn::Result<TokenStream> {
+    let sig = &function.sig;
+    if sig.asyncness.is_some()
+        || !sig.generics.params.is_empty()
+        || !matches!(sig.output, ReturnType::Default)
+    {
+        return Err(syn::Error::new(
+            sig.span(),
+            "#[crossroads(proptest)] can only be used with functions without generic parameters, async or a return type.",
+        ));
+    }
+    if sig.inputs.is_empty() {
+        return Err(syn::Error::new(
+            sig.span(),
+            "#[crossroads(proptest)] needs at least one argument to generate values for.",
+        ));
+    }
+    if let Some(receiver) = sig.receiver() {
+        return Err(syn::Error::new(
+            receiver.span(),
+            "#[crossroads(proptest)] cannot be used with methods.",
+        ));
+    }
+
+    let inputs = sig.inputs.iter().filter_map(|input| match input {
+        FnArg::Typed(input) => Some(input),
+        FnArg::Receiver(_) => None,
+    });
+    let inputs = inputs.map(|input| {
+        let strategy: Expr = match input
+            .attrs
+            .iter()
+            .find(|attr| attr.path.is_ident("strategy"))
+        {
+            Some(attr) => attr.parse_args().unwrap_or_else(|error| {
+                panic!(
+                    "Expected a strategy within #[strategy(..)]: {} {:?}",
+                    error,
+                    attr.span()
+                )
+            }),
+            None => {
+                let ty = &input.ty;
+                parse_quote!(::proptest::arbitrary::any::<#ty>())
+            }
+        };
+        // Inputs that are not used along this path would otherwise cause a warning within the block.
+        let pat: Pat = match input.pat.as_ref() {
+            Pat::Ident(pat) if !mentions(function.block.to_token_stream(), &pat.ident) => {
+                parse_quote!(_)
+            }
+            pat => pat.clone(),
+        };
+        quote!(#pat in #strategy)
+    });
+
+    let attrs = &function.attrs;
+    let name = &sig.ident;
+    let block = &function.block;
+    let config = proptest
+        .config
+        .as_ref()
+        .map(|config| quote!(#![proptest_config(#config)]));
+    Ok(quote! {
+        ::proptest::proptest! {
+            #config
+            #(#attrs)*
+            fn #name(#(#inputs),*) #block
+        }
+    })
+}
@@ -0,0 +1,238 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! Shared fork sets, i.e. ```fork_set!(name { .. })``` and their uses as ```fork!(name)```.
+//!
+//! Proc macros cannot share any state, so a set is defined as a ```macro_rules!``` macro that passes its
+//! arms on to a callback. When ```#[crossroads]``` finds a reference to a set, it hands the whole item to
+//! the set's macro, which calls back into [resolve_fork_set] with the arms appended. This resolves one set
+//! at a time and then runs the expansion again, which picks up the next set (if any).
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{
+    braced, bracketed, parenthesized, parse_quote, visit_mut, Arm, Expr, ExprMatch, Ident, Item,
+    Macro, Path, Token, Visibility,
+};
+
+use crate::fork::is_fork;
+
+/// A reference to a fork set within a ```fork!(..)```, i.e. either ```fork!(name)``` or ```fork!(use name)```.
+struct Reference {
+    path: Path,
+}
+
+impl Parse for Reference {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let _: Option<Token![use]> = input.parse()?;
+        let path = input.call(Path::parse_mod_style)?;
+        Ok(Self { path })
+    }
+}
+
+fn reference(mac: &Macro) -> Option<Path> {
+    if !is_fork(mac) || mac.tokens.is_empty() {
+        return None;
+    }
+    mac.parse_body::<Reference>()
+        .ok()
+        .map(|reference| reference.path)
+}
+
+/// The arms of a fork set, as in ```vec => Vec::new(), deque => VecDeque::new()```.
+struct Arms(Vec<Arm>);
+
+impl Parse for Arms {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut arms = Vec::new();
+        while !input.is_empty() {
+            arms.push(input.parse()?);
+        }
+        Ok(Self(arms))
+    }
+}
+
+/// The input of ```fork_set!(pub name { .. })```.
+struct Definition {
+    vis: Visibility,
+    name: Ident,
+    arms: TokenStream,
+}
+
+impl Parse for Definition {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+        let name: Ident = input.parse()?;
+        let content;
+        braced!(content in input);
+        let arms: TokenStream = content.parse()?;
+        let parsed: Arms = syn::parse2(arms.clone())?;
+        if parsed.0.is_empty() {
+            return Err(syn::Error::new(
+                name.span(),
+                "A fork set must contain at least one arm.",
+            ));
+        }
+        Ok(Self { vis, name, arms })
+    }
+}
+
+/// The full expansion of ```fork_set!(input)```, which defines a ```macro_rules!``` macro named after the
+/// set. Public sets are exported from the crate (```#[macro_export]```), all others can be referred to by
+/// path within the crate.
+pub fn fork_set(input: TokenStream) -> TokenStream {
+    let Definition { vis, name, arms } = match syn::parse2(input) {
+        Ok(definition) => definition,
+        Err(error) => return error.to_compile_error(),
+    };
+
+    let rules = quote! {
+        ({ $($callback:tt)* } $($args:tt)*) => {
+            $($callback)*! { $($args)* { #arms } }
+        };
+    };
+    match vis {
+        Visibility::Public(_) => quote! {
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! #name { #rules }
+        },
+        _ => quote! {
+            #[allow(unused_macros)]
+            macro_rules! #name { #rules }
+            #[allow(unused_imports)]
+            #vis use #name;
+        },
+    }
+}
+
+/// Finds the first reference to a fork set within an item.
+#[derive(Default)]
+struct FirstReference {
+    path: Option<Path>,
+}
+
+impl<'ast> Visit<'ast> for FirstReference {
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        if self.path.is_none() {
+            self.path = reference(mac);
+        }
+    }
+}
+
+/// Hands the item over to the macro of the first fork set it refers to, if any.
+pub(crate) fn defer(args: &TokenStream, item: &Item) -> Option<TokenStream> {
+    let mut first = FirstReference::default();
+    first.visit_item(item);
+    let path = first.path?;
+    Some(quote! {
+        #path! { { ::crossroads::__resolve_fork_set } [#path] (#args) { #item } }
+    })
+}
+
+/// Replaces all references to the given fork set with a ```match fork!() { .. }``` over its arms.
+struct Substitution<'a> {
+    path: String,
+    arms: &'a [Arm],
+}
+
+impl Substitution<'_> {
+    fn refers(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Macro(mac) => reference(&mac.mac)
+                .map(|path| path.to_token_stream().to_string() == self.path)
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+impl VisitMut for Substitution<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Match(mtch) = expr {
+            // Arms listed in place are added to the ones of the set.
+            if self.refers(&mtch.expr) {
+                let mut arms = self.arms.to_vec();
+                arms.append(&mut mtch.arms);
+                mtch.arms = arms;
+                *mtch.expr = parse_quote!(fork!());
+            }
+        } else if self.refers(expr) {
+            *expr = Expr::Match(ExprMatch {
+                attrs: vec![],
+                match_token: Default::default(),
+                expr: parse_quote!(fork!()),
+                brace_token: Default::default(),
+                arms: self.arms.to_vec(),
+            });
+        }
+        visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+/// The input of the callback, i.e. ```[path] (args) { item } { arms }```.
+struct Resolution {
+    path: Path,
+    args: TokenStream,
+    item: Item,
+    arms: Arms,
+}
+
+impl Parse for Resolution {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let (path, args, item, arms);
+        bracketed!(path in input);
+        parenthesized!(args in input);
+        braced!(item in input);
+        braced!(arms in input);
+        Ok(Self {
+            path: path.call(Path::parse_mod_style)?,
+            args: args.parse()?,
+            item: item.parse()?,
+            arms: arms.parse()?,
+        })
+    }
+}
+
+/// The callback invoked by the macro of a fork set, which substitutes the set within the item and
+/// expands the result as ```#[crossroads(args)]``` would.
+pub fn resolve_fork_set(input: TokenStream) -> TokenStream {
+    let Resolution {
+        path,
+        args,
+        mut item,
+        arms,
+    } = match syn::parse2(input) {
+        Ok(resolution) => resolution,
+        Err(error) => return error.to_compile_error(),
+    };
+
+    Substitution {
+        path: path.to_token_stream().to_string(),
+        arms: &arms.0,
+    }
+    .visit_item_mut(&mut item);
+    crate::crossroads(args, item.into_token_stream())
+}
@@ -0,0 +1,300 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::collections::HashSet;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{
+    parse_quote, visit, Expr, FnArg, Ident, ItemFn, Local, PatIdent, ReturnType, Stmt, Type,
+};
+
+use crate::fork::{alternatives, reject_type_forks, Alternative};
+
+/// A variable that is bound before a fork point, and which every arm but the last one gets its own clone of.
+#[derive(Clone)]
+struct Binding {
+    ident: Ident,
+    mutable: bool,
+}
+
+#[derive(Default)]
+struct Bindings(Vec<Binding>);
+
+impl<'ast> Visit<'ast> for Bindings {
+    fn visit_pat_ident(&mut self, pat: &'ast PatIdent) {
+        if pat.by_ref.is_none() {
+            self.0.retain(|binding| binding.ident != pat.ident);
+            self.0.push(Binding {
+                ident: pat.ident.clone(),
+                mutable: pat.mutability.is_some(),
+            });
+        }
+        visit::visit_pat_ident(self, pat);
+    }
+}
+
+#[derive(Default)]
+struct ForkFinder {
+    found: bool,
+}
+
+impl<'ast> Visit<'ast> for ForkFinder {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if alternatives(expr).is_some() {
+            self.found = true;
+        } else {
+            visit::visit_expr(self, expr);
+        }
+    }
+}
+
+fn contains_fork(stmt: &Stmt) -> bool {
+    let mut finder = ForkFinder::default();
+    finder.visit_stmt(stmt);
+    finder.found
+}
+
+fn idents(tokens: TokenStream, into: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                into.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => idents(group.stream(), into),
+            _ => {}
+        }
+    }
+}
+
+/// Turns the replacement of a fork point into statements, so that fork points within it end up on the same
+/// level as the remainder of the function. Replacements without fork points are kept as a single block.
+fn inline(replacement: Expr) -> Vec<Stmt> {
+    match replacement {
+        Expr::Block(block) if block.attrs.is_empty() && block.label.is_none() => {
+            let mut stmts = block.block.stmts;
+            if let [Stmt::Expr(Expr::Block(_))] = stmts.as_slice() {
+                if let Some(Stmt::Expr(inner)) = stmts.pop() {
+                    return inline(inner);
+                }
+            }
+            stmts
+        }
+        replacement => vec![Stmt::Expr(replacement)],
+    }
+}
+
+/// The statements that continue after choosing the given alternative for a fork point in ```stmt```.
+fn continuation(stmt: &Stmt, alternative: Alternative, is_tail: bool) -> Vec<Stmt> {
+    let replacement = alternative.replacement;
+    let needs_inlining = contains_fork(&Stmt::Expr(replacement.clone()));
+    match stmt {
+        Stmt::Local(local) => {
+            let mut stmts = if needs_inlining {
+                inline(replacement)
+            } else {
+                vec![Stmt::Expr(replacement)]
+            };
+            let init = match stmts.pop() {
+                Some(Stmt::Expr(init)) => init,
+                other => panic!(
+                    "#[crossroads(clone)] requires the arms of a fork!() in a let statement to end with an expression. {:?}",
+                    other.span()
+                ),
+            };
+            let mut local = Local::clone(local);
+            local.init = Some((Default::default(), Box::new(init)));
+            stmts.push(Stmt::Local(local));
+            stmts
+        }
+        _ => {
+            let mut stmts = if needs_inlining {
+                inline(replacement)
+            } else {
+                vec![Stmt::Expr(replacement)]
+            };
+            if !is_tail {
+                if let Some(Stmt::Expr(last)) = stmts.pop() {
+                    stmts.push(Stmt::Semi(last, Default::default()));
+                }
+            }
+            stmts
+        }
+    }
+}
+
+/// The alternatives if the statement itself is a fork point, or if it is a ```let``` statement that is
+/// initialized with one.
+fn statement_alternatives(stmt: &Stmt) -> Option<Vec<Alternative>> {
+    match stmt {
+        Stmt::Local(Local {
+            init: Some((_, init)),
+            ..
+        }) => alternatives(init),
+        Stmt::Expr(expr) | Stmt::Semi(expr, _) => alternatives(expr),
+        _ => None,
+    }
+}
+
+/// Rewrites a function so that everything before a fork point runs only once, while each arm (and
+/// the remainder of the function) continues from its own clone of the local variables.
+struct Snapshot {
+    function: String,
+    output: Type,
+    report: Ident,
+}
+
+impl Snapshot {
+    /// The closure that runs ```body``` for a single path with the report as its argument.
+    fn closure(&self, body: Vec<Stmt>) -> Expr {
+        let mut used = HashSet::new();
+        for stmt in &body {
+            idents(stmt.to_token_stream(), &mut used);
+        }
+
+        let output = &self.output;
+        if used.contains(&self.report.to_string()) {
+            let report = &self.report;
+            parse_quote!(|#report| -> #output { #(#body)* })
+        } else {
+            parse_quote!(|_| -> #output { #(#body)* })
+        }
+    }
+
+    fn section(&self, path: &[String], bindings: &Bindings, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        let mut bindings = Bindings(bindings.0.clone());
+        let mut section = Vec::with_capacity(stmts.len());
+
+        let mut stmts = stmts.into_iter();
+        while let Some(stmt) = stmts.next() {
+            if let Some(alternatives) = statement_alternatives(&stmt) {
+                let rest: Vec<Stmt> = stmts.collect();
+                let is_tail = rest.is_empty() && matches!(stmt, Stmt::Expr(_));
+                let report = &self.report;
+                let last = alternatives.len() - 1;
+
+                for (index, alternative) in alternatives.into_iter().enumerate() {
+                    let mut path = path.to_vec();
+                    path.push(alternative.segment.clone());
+                    let name = std::iter::once(self.function.clone())
+                        .chain(path.iter().cloned())
+                        .collect::<Vec<String>>()
+                        .join("_");
+
+                    let mut body = continuation(&stmt, alternative, is_tail);
+                    body.extend(rest.iter().cloned());
+                    let body = self.section(&path, &bindings, body);
+
+                    // The last arm can simply continue with the original values.
+                    let mut used = HashSet::new();
+                    if index != last {
+                        for stmt in &body {
+                            idents(stmt.to_token_stream(), &mut used);
+                        }
+                    }
+                    let clones = bindings
+                        .0
+                        .iter()
+                        .filter(|binding| used.contains(&binding.ident.to_string()))
+                        .map(|binding| -> Stmt {
+                            let ident = &binding.ident;
+                            if binding.mutable {
+                                parse_quote!(let mut #ident = ::core::clone::Clone::clone(&#ident);)
+                            } else {
+                                parse_quote!(let #ident = ::core::clone::Clone::clone(&#ident);)
+                            }
+                        });
+
+                    let body = self.closure(body);
+                    section.push(parse_quote!({
+                        #(#clones)*
+                        #report.branch(#name, #body);
+                    }));
+                }
+
+                let output = &self.output;
+                section.push(Stmt::Expr(parse_quote!(
+                    <#output as ::crossroads_runtime::Outcome>::success()
+                )));
+                return section;
+            }
+
+            assert!(
+                !contains_fork(&stmt),
+                "#[crossroads(clone)] requires fork!() to be used as a statement or to initialize a let statement, either in the function body or directly within the arm of another fork!(). {:?}",
+                stmt.span()
+            );
+            if let Stmt::Local(local) = &stmt {
+                bindings.visit_pat(&local.pat);
+            }
+            section.push(stmt);
+        }
+
+        section
+    }
+}
+
+/// Builds a single function that runs everything before a fork point only once, with every arm
+/// continuing from its own clone of the local variables. The result of every path is reported
+/// through a ```crossroads_runtime::Report```.
+pub(crate) fn snapshot(original: &ItemFn) -> syn::Result<ItemFn> {
+    let sig = &original.sig;
+    if sig.asyncness.is_some() {
+        return Err(syn::Error::new(
+            sig.span(),
+            "#[crossroads(clone)] can not be used with async functions.",
+        ));
+    }
+    reject_type_forks(original, "clone");
+
+    let mut bindings = Bindings::default();
+    for input in &sig.inputs {
+        if let FnArg::Typed(input) = input {
+            bindings.visit_pat(&input.pat);
+        }
+    }
+
+    let snapshot = Snapshot {
+        function: sig.ident.to_string(),
+        output: match &sig.output {
+            ReturnType::Default => parse_quote!(()),
+            ReturnType::Type(_, ty) => ty.as_ref().clone(),
+        },
+        report: Ident::new("crossroads_report", sig.ident.span()),
+    };
+    let body = snapshot.section(&[], &bindings, original.block.stmts.clone());
+
+    let name = &snapshot.function;
+    let report = &snapshot.report;
+    let body = snapshot.closure(body);
+
+    let mut function = original.clone();
+    function.sig.output = ReturnType::Default;
+    *function.block = parse_quote!({
+        let mut #report = ::crossroads_runtime::Report::new(#name);
+        #report.branch(#name, #body);
+        #report.finish();
+    });
+    Ok(function)
+}
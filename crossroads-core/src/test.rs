@@ -0,0 +1,149 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::{parse_quote, Attribute, Item, ItemFn, Path, ReturnType};
+
+use crate::options::{Options, Output};
+
+/// Splits the ```async_runtime = path``` argument off the arguments of ```#[crossroads::test]```, which
+/// leaves the ones that are handed on to ```#[crossroads]```.
+fn async_runtime(args: TokenStream) -> syn::Result<(Option<Path>, TokenStream)> {
+    let mut arguments: Vec<Vec<TokenTree>> = vec![vec![]];
+    for token in args {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => arguments.push(vec![]),
+            _ => arguments.last_mut().expect("Never empty.").push(token),
+        }
+    }
+
+    let mut runtime = None;
+    let mut rest = TokenStream::new();
+    for argument in arguments
+        .into_iter()
+        .filter(|argument| !argument.is_empty())
+    {
+        let mut tokens = argument.iter().cloned();
+        match tokens.next() {
+            Some(TokenTree::Ident(ident)) if ident == "async_runtime" => {
+                if !matches!(tokens.next(), Some(TokenTree::Punct(punct)) if punct.as_char() == '=')
+                {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "Expected `async_runtime = path`, e.g. `async_runtime = tokio::test`.",
+                    ));
+                }
+                runtime = Some(syn::parse2(tokens.collect())?);
+            }
+            _ => {
+                if !rest.is_empty() {
+                    rest.extend(quote::quote!(,));
+                }
+                rest.extend(argument);
+            }
+        }
+    }
+    Ok((runtime, rest))
+}
+
+/// Whether the attribute is one of those that parametrize a test and generate the ```#[test]```s
+/// themselves, i.e. ```#[rstest]```, ```#[test_case(..)]``` and ```#[test_matrix(..)]```.
+fn is_parametrized(attr: &Attribute) -> bool {
+    matches!(
+        attr.path.segments.last(),
+        Some(segment) if ["rstest", "test_case", "test_matrix"].iter().any(|name| segment.ident == name)
+    )
+}
+
+/// The full expansion of ```#[crossroads::test(args)] input```, which marks every generated function as
+/// a test before expanding it like ```#[crossroads(args)]``` would.
+pub fn test(args: TokenStream, input: TokenStream) -> TokenStream {
+    let (runtime, args) = match async_runtime(args) {
+        Ok(split) => split,
+        Err(error) => return error.to_compile_error(),
+    };
+    let options: Options = match syn::parse2(args.clone()) {
+        Ok(options) => options,
+        Err(error) => return error.to_compile_error(),
+    };
+    if options.output == Output::Dispatch {
+        return syn::Error::new(
+            args.span(),
+            "#[crossroads::test] cannot be combined with `enum`, as that takes the path as an argument.",
+        )
+        .to_compile_error();
+    }
+    let mut function: ItemFn = match syn::parse2(input) {
+        Ok(Item::Fn(function)) => function,
+        Ok(item) => {
+            return syn::Error::new(
+                item.span(),
+                "#[crossroads::test] can only be used with functions.",
+            )
+            .to_compile_error()
+        }
+        Err(error) => return error.to_compile_error(),
+    };
+    if let Some(attr) = function
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("test"))
+    {
+        return syn::Error::new(
+            attr.span(),
+            "#[crossroads::test] already adds #[test] to every generated function.",
+        )
+        .to_compile_error();
+    }
+
+    if function.attrs.iter().any(is_parametrized) {
+        // These generate the tests themselves, including those for async functions.
+        return crate::crossroads(args, function.into_token_stream());
+    }
+
+    let attr: Attribute = match (function.sig.asyncness.is_some(), runtime) {
+        (true, Some(runtime)) => parse_quote!(#[#runtime]),
+        (true, None) => {
+            // Without a runtime of its own, the body is run to completion by a minimal executor.
+            function.sig.asyncness = None;
+            let block = &function.block;
+            *function.block = match &function.sig.output {
+                ReturnType::Type(_, ty) => parse_quote!({
+                    ::crossroads_runtime::block_on(async move {
+                        let output: #ty = #block;
+                        output
+                    })
+                }),
+                ReturnType::Default => {
+                    parse_quote!({ ::crossroads_runtime::block_on(async move #block) })
+                }
+            };
+            parse_quote!(#[test])
+        }
+        (false, _) => parse_quote!(#[test]),
+    };
+    function.attrs.insert(0, attr);
+
+    crate::crossroads(args, function.into_token_stream())
+}
@@ -0,0 +1,112 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::{parse_quote, visit_mut, Ident, Item, ItemMod, Token, Type, TypeParam};
+
+use crate::names::type_segment;
+use crate::options::Instantiation;
+
+/// Replaces the ```_``` placeholders within a type with generic parameters ```T0```, ```T1```, ...
+#[derive(Default)]
+struct Placeholders {
+    params: Vec<Ident>,
+}
+
+impl VisitMut for Placeholders {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Infer(infer) = ty {
+            let param = Ident::new(&format!("T{}", self.params.len()), infer.span());
+            *ty = parse_quote!(#param);
+            self.params.push(param);
+        } else {
+            visit_mut::visit_type_mut(self, ty);
+        }
+    }
+}
+
+/// The full expansion of ```#[crossroads::typed(args)] input```, which duplicates a module once per
+/// combination of the given types, with a type alias for each of them.
+pub fn typed(args: TokenStream, input: TokenStream) -> TokenStream {
+    let instantiations = match Punctuated::<Instantiation, Token![,]>::parse_terminated.parse2(args)
+    {
+        Ok(instantiations) => instantiations,
+        Err(error) => return error.to_compile_error(),
+    };
+    let module: ItemMod = match syn::parse2(input) {
+        Ok(module) => module,
+        Err(error) => return error.to_compile_error(),
+    };
+    if module.content.is_none() {
+        return syn::Error::new(
+            module.span(),
+            "#[crossroads::typed] can only be used with inline modules, i.e. mod name { .. }.",
+        )
+        .to_compile_error();
+    }
+
+    let mut modules = vec![module];
+    for instantiation in &instantiations {
+        let alias = &instantiation.param;
+        let mut new_modules = Vec::new();
+        for ty in &instantiation.types {
+            for module in &modules {
+                let segment = type_segment(ty);
+                let mut ty = ty.clone();
+                let mut placeholders = Placeholders::default();
+                placeholders.visit_type_mut(&mut ty);
+                let params: Vec<TypeParam> = placeholders
+                    .params
+                    .into_iter()
+                    .map(TypeParam::from)
+                    .collect();
+
+                let mut module = module.clone();
+                module.ident = Ident::new(
+                    &format!("{}_{}", module.ident, segment),
+                    module.ident.span(),
+                );
+                let item: Item = if params.is_empty() {
+                    parse_quote!(#[allow(dead_code)] type #alias = #ty;)
+                } else {
+                    parse_quote!(#[allow(dead_code)] type #alias<#(#params),*> = #ty;)
+                };
+                if let Some((_, items)) = &mut module.content {
+                    items.insert(0, item);
+                }
+                new_modules.push(module);
+            }
+        }
+        modules = new_modules;
+    }
+
+    let mut tokens = TokenStream::new();
+    for module in modules {
+        module.to_tokens(&mut tokens);
+    }
+    tokens
+}
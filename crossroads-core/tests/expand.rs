@@ -0,0 +1,851 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crossroads_core::{enumerate_paths, expand, Path};
+use quote::ToTokens;
+use syn::{parse_quote, ItemFn};
+
+fn segments(paths: &[Path]) -> Vec<Vec<&str>> {
+    paths
+        .iter()
+        .map(|path| path.segments().iter().map(String::as_str).collect())
+        .collect()
+}
+
+fn nested() -> ItemFn {
+    parse_quote! {
+        fn empty() {
+            let mut map = HashMap::<String, usize>::default();
+
+            match fork!() {
+                by_default => {}
+                after_add => {
+                    map.insert("Key".to_owned(), 1337);
+                    match fork!() {
+                        and_remove => map.remove("Key"),
+                        and_clear => map.clear(),
+                    };
+                }
+            }
+
+            assert!(map.is_empty());
+        }
+    }
+}
+
+#[test]
+fn enumerates_nested_paths_in_order() {
+    assert_eq!(
+        vec![
+            vec!["by_default"],
+            vec!["after_add", "and_remove"],
+            vec!["after_add", "and_clear"],
+        ],
+        segments(&enumerate_paths(&nested()))
+    );
+}
+
+#[test]
+fn enumerates_sequential_forks_as_product() {
+    let function: ItemFn = parse_quote! {
+        fn product() -> (Mode, usize) {
+            let mode = fork!(Mode::{Fast, Safe});
+            let value = match fork!() {
+                one => 1,
+                two => 2,
+            };
+            (mode, value)
+        }
+    };
+
+    assert_eq!(
+        vec![
+            vec!["mode_fast", "one"],
+            vec!["mode_safe", "one"],
+            vec!["mode_fast", "two"],
+            vec!["mode_safe", "two"],
+        ],
+        segments(&enumerate_paths(&function))
+    );
+}
+
+#[test]
+fn function_without_forks_has_a_single_empty_path() {
+    let function: ItemFn = parse_quote! {
+        fn plain() {}
+    };
+
+    let paths = enumerate_paths(&function);
+    assert_eq!(vec![Path::default()], paths);
+    assert_eq!(
+        function.to_token_stream().to_string(),
+        expand(&function, &paths[0]).to_token_stream().to_string()
+    );
+}
+
+#[test]
+fn expands_along_path() {
+    let path = Path::new(vec!["after_add".to_owned(), "and_clear".to_owned()]);
+    let expected: ItemFn = parse_quote! {
+        fn empty_after_add_and_clear() {
+            let mut map = HashMap::<String, usize>::default();
+
+            {
+                {
+                    map.insert("Key".to_owned(), 1337);
+                    {
+                        map.clear()
+                    };
+                }
+            }
+
+            assert!(map.is_empty());
+        }
+    };
+
+    assert_eq!(
+        expected.to_token_stream().to_string(),
+        expand(&nested(), &path).to_token_stream().to_string()
+    );
+}
+
+#[test]
+fn expands_enum_values() {
+    let function: ItemFn = parse_quote! {
+        fn run() -> Mode {
+            fork!(Mode::{Fast, Safe})
+        }
+    };
+    let expected: ItemFn = parse_quote! {
+        fn run_mode_safe() -> Mode {
+            Mode::Safe
+        }
+    };
+
+    let paths = enumerate_paths(&function);
+    assert_eq!(
+        expected.to_token_stream().to_string(),
+        expand(&function, &paths[1]).to_token_stream().to_string()
+    );
+}
+
+#[test]
+#[should_panic(expected = "Must use only idents or paths to enum variants with a fork!() match!")]
+fn rejects_other_patterns() {
+    let function: ItemFn = parse_quote! {
+        fn literal() {
+            match fork!() {
+                1 => {}
+            }
+        }
+    };
+
+    enumerate_paths(&function);
+}
+
+#[test]
+fn reports_unknown_options() {
+    let output = crossroads_core::crossroads(quote::quote!(unknown), nested().to_token_stream());
+
+    assert!(output.to_string().contains("compile_error"));
+    assert!(output.to_string().contains("Unknown option `unknown`"));
+}
+
+#[test]
+fn reports_unsupported_options() {
+    let item: syn::ItemImpl = parse_quote! {
+        impl Backend for Store {
+            fn get(&self) -> usize {
+                match fork!() {
+                    one => 1,
+                    two => 2,
+                }
+            }
+        }
+    };
+    let output = crossroads_core::crossroads(quote::quote!(all), item.to_token_stream());
+    assert!(output.to_string().contains("compile_error"));
+    assert!(output
+        .to_string()
+        .contains("#[crossroads] does not support any options but max_paths for impl blocks."));
+
+    let function: ItemFn = parse_quote! {
+        fn sized(size: usize) {
+            match fork!() {
+                one => {}
+                two => {}
+            }
+        }
+    };
+    let output =
+        crossroads_core::crossroads(quote::quote!(bench = divan), function.to_token_stream());
+    assert!(output.to_string().contains("compile_error"));
+    assert!(output.to_string().contains(
+        "#[crossroads(bench = divan)] can only be used with functions without arguments, generic parameters or async."
+    ));
+}
+
+#[test]
+fn emits_variants_on_request() {
+    // Associated functions without self can not be told apart from free functions, so nothing refers to the
+    // generated functions by their plain names unless asked to.
+    let function: ItemFn = parse_quote! {
+        pub fn make() -> u8 {
+            match fork!() {
+                one => 1,
+                two => 2,
+            }
+        }
+    };
+    let output = crossroads_core::crossroads(quote::quote!(), function.to_token_stream());
+    assert!(!output.to_string().contains("MAKE_VARIANTS"));
+
+    let output = crossroads_core::crossroads(quote::quote!(variants), function.to_token_stream());
+    let output = output.to_string();
+    assert!(output.contains("pub const MAKE_VARIANTS"));
+    assert!(output.contains(
+        "# [doc = \"The functions generated for every path through ```make```, along with their names.\"]"
+    ));
+
+    let function: ItemFn = parse_quote! {
+        fn sized(size: usize) {
+            match fork!() {
+                one => {}
+                two => {}
+            }
+        }
+    };
+    let output = crossroads_core::crossroads(quote::quote!(variants), function.to_token_stream());
+    assert!(output.to_string().contains(
+        "#[crossroads(variants)] can only be used with free functions without arguments, generic parameters or async."
+    ));
+
+    let output =
+        crossroads_core::crossroads(quote::quote!(enum, variants), nested().to_token_stream());
+    assert!(output
+        .to_string()
+        .contains("`variants` cannot be combined with `enum`."));
+}
+
+#[test]
+fn reports_conflicting_options() {
+    let output = crossroads_core::crossroads(quote::quote!(clone, all), nested().to_token_stream());
+    assert!(output
+        .to_string()
+        .contains("`all` cannot be combined with `clone`."));
+
+    let output = crossroads_core::crossroads(
+        quote::quote!(runtime, bench = divan),
+        nested().to_token_stream(),
+    );
+    assert!(output
+        .to_string()
+        .contains("`bench` cannot be combined with `runtime`."));
+}
+
+#[test]
+fn enumerates_permutations_in_order() {
+    let function: ItemFn = parse_quote! {
+        fn ordering() {
+            let mut map = HashMap::new();
+            fork_permutations! {
+                #[label(insert_a)]
+                map.insert(a, 1);
+                #[label(insert_b)]
+                map.insert(b, 2);
+                map.remove(&a);
+            }
+        }
+    };
+
+    let paths = enumerate_paths(&function);
+    assert_eq!(
+        vec![
+            vec!["insert_a_insert_b_s2"],
+            vec!["insert_a_s2_insert_b"],
+            vec!["insert_b_insert_a_s2"],
+            vec!["insert_b_s2_insert_a"],
+            vec!["s2_insert_a_insert_b"],
+            vec!["s2_insert_b_insert_a"],
+        ],
+        segments(&paths)
+    );
+
+    let expected: ItemFn = parse_quote! {
+        fn ordering_s2_insert_a_insert_b() {
+            let mut map = HashMap::new();
+            {
+                map.remove(&a);
+                map.insert(a, 1);
+                map.insert(b, 2);
+            }
+        }
+    };
+    assert_eq!(
+        expected.to_token_stream().to_string(),
+        expand(&function, &paths[4]).to_token_stream().to_string()
+    );
+}
+
+#[test]
+fn limits_number_of_paths() {
+    let function: ItemFn = parse_quote! {
+        fn ordering() {
+            fork_permutations! {
+                a();
+                b();
+                c();
+            }
+            match fork!() {
+                once => {}
+                twice => {}
+            }
+        }
+    };
+
+    let output = std::panic::catch_unwind(|| {
+        crossroads_core::crossroads(quote::quote!(max_paths = 10), function.to_token_stream())
+    });
+    assert!(output.is_err());
+
+    let output =
+        crossroads_core::crossroads(quote::quote!(max_paths = 12), function.to_token_stream());
+    assert_eq!(12, output.to_string().matches("fn ordering_").count());
+}
+
+#[test]
+fn enumerates_subsets_in_order() {
+    let function: ItemFn = parse_quote! {
+        fn cache() {
+            fork_subsets! {
+                #[label(warm)]
+                cache.warm();
+                #[label(evict)]
+                cache.evict();
+            }
+        }
+    };
+
+    let paths = enumerate_paths(&function);
+    assert_eq!(
+        vec![
+            vec!["with_none"],
+            vec!["with_warm"],
+            vec!["with_evict"],
+            vec!["with_warm_evict"],
+        ],
+        segments(&paths)
+    );
+
+    let expected: ItemFn = parse_quote! {
+        fn cache_with_evict() {
+            {
+                cache.evict();
+            }
+        }
+    };
+    assert_eq!(
+        expected.to_token_stream().to_string(),
+        expand(&function, &paths[2]).to_token_stream().to_string()
+    );
+}
+
+#[test]
+fn enumerates_interleavings_in_order() {
+    let function: ItemFn = parse_quote! {
+        fn actors() {
+            fork_interleave! {
+                a: {
+                    machine.send(1);
+                    machine.send(2);
+                },
+                b: { machine.cancel() },
+            }
+        }
+    };
+
+    let paths = enumerate_paths(&function);
+    assert_eq!(
+        vec![vec!["a_a_b"], vec!["a_b_a"], vec!["b_a_a"]],
+        segments(&paths)
+    );
+
+    let expected: ItemFn = parse_quote! {
+        fn actors_a_b_a() {
+            {
+                machine.send(1);
+                machine.cancel();
+                machine.send(2);
+            }
+        }
+    };
+    assert_eq!(
+        expected.to_token_stream().to_string(),
+        expand(&function, &paths[1]).to_token_stream().to_string()
+    );
+}
+
+#[test]
+fn enumerates_values() {
+    let function: ItemFn = parse_quote! {
+        fn boundary() {
+            let n = fork!(in -1..=1);
+            let m: u8 = fork!(in 0u8..2);
+            check(n, m, fork!(in [0, usize::MAX - 1, usize::MAX] as edge));
+        }
+    };
+
+    let paths = enumerate_paths(&function);
+    assert_eq!(18, paths.len());
+    assert_eq!(vec!["n_minus_1", "m_0", "edge_0"], segments(&paths)[0]);
+    assert_eq!(vec!["n_1", "m_1", "edge_2"], segments(&paths)[17]);
+
+    let expected: ItemFn = parse_quote! {
+        fn boundary_n_minus_1_m_1_edge_1() {
+            let n = -1;
+            let m: u8 = 1u8;
+            check(n, m, usize::MAX - 1);
+        }
+    };
+    assert_eq!(
+        expected.to_token_stream().to_string(),
+        expand(
+            &function,
+            &Path::new(vec![
+                "n_minus_1".to_owned(),
+                "m_1".to_owned(),
+                "edge_1".to_owned()
+            ])
+        )
+        .to_token_stream()
+        .to_string()
+    );
+}
+
+#[test]
+#[should_panic(expected = "The bounds of a range in fork!(in ..) must be integer literals.")]
+fn rejects_non_literal_ranges() {
+    let function: ItemFn = parse_quote! {
+        fn boundary() {
+            let n = fork!(in 0..LIMIT);
+        }
+    };
+
+    enumerate_paths(&function);
+}
+
+#[test]
+#[should_panic(
+    expected = "The bounds of a range in fork!(in ..) must have the same suffix, found `u8` and `u16`."
+)]
+fn rejects_mismatched_range_suffixes() {
+    let function: ItemFn = parse_quote! {
+        fn boundary() {
+            let n = fork!(in 0u8..2u16);
+        }
+    };
+
+    enumerate_paths(&function);
+}
+
+#[test]
+fn expands_type_forks() {
+    let function: ItemFn = parse_quote! {
+        fn collection() {
+            type Map = fork_type!(hash = HashMap<String, usize>, btree = BTreeMap<String, usize>);
+            let map: Vec<fork_type!(small = u8, large = u64)> = Map::default();
+        }
+    };
+
+    let paths = enumerate_paths(&function);
+    assert_eq!(
+        vec![
+            vec!["hash", "small"],
+            vec!["btree", "small"],
+            vec!["hash", "large"],
+            vec!["btree", "large"],
+        ],
+        segments(&paths)
+    );
+
+    let expected: ItemFn = parse_quote! {
+        fn collection_btree_large() {
+            type Map = BTreeMap<String, usize>;
+            let map: Vec<u64> = Map::default();
+        }
+    };
+    assert_eq!(
+        expected.to_token_stream().to_string(),
+        expand(&function, &paths[3]).to_token_stream().to_string()
+    );
+}
+
+#[test]
+#[should_panic(expected = "fork_type!() cannot be used with #[crossroads(runtime)]")]
+fn rejects_type_forks_at_runtime() {
+    let function: ItemFn = parse_quote! {
+        fn collection() {
+            type Map = fork_type!(hash = HashMap<String, usize>, btree = BTreeMap<String, usize>);
+        }
+    };
+
+    crossroads_core::crossroads(quote::quote!(runtime), function.to_token_stream());
+}
+
+#[test]
+fn rejects_type_forks_in_shared_signatures() {
+    let function: ItemFn = parse_quote! {
+        fn sized(value: fork_type!(narrow = u8, wide = u16)) -> usize {
+            std::mem::size_of_val(&value)
+        }
+    };
+
+    let output = crossroads_core::crossroads(quote::quote!(enum), function.to_token_stream());
+    assert!(output.to_string().contains(
+        "fork_type!() cannot be used within the signature with #[crossroads(enum)], as that keeps a single signature for all paths."
+    ));
+
+    let output = crossroads_core::crossroads(quote::quote!(), function.to_token_stream());
+    assert!(output.to_string().contains("fn sized_wide (value : u16)"));
+}
+
+#[test]
+fn instantiates_generic_parameters() {
+    let function: ItemFn = parse_quote! {
+        fn roundtrip<'a, T: Codec>(input: &'a T) -> Vec<T> {
+            vec![T::decode(input)]
+        }
+    };
+
+    let output = crossroads_core::crossroads(
+        quote::quote!(types(T = [Vec<String>, &'static str])),
+        function.to_token_stream(),
+    );
+
+    let expected: syn::File = parse_quote! {
+        fn roundtrip_vec_string<'a>(input: &'a Vec<String>) -> Vec<Vec<String> >
+        where
+            Vec<String>: Codec
+        {
+            vec![<Vec<String> >::decode(input)]
+        }
+
+        fn roundtrip_str<'a>(input: &'a &'static str) -> Vec<&'static str>
+        where
+            &'static str: Codec
+        {
+            vec![<&'static str>::decode(input)]
+        }
+    };
+    assert!(output
+        .to_string()
+        .ends_with(&expected.to_token_stream().to_string()));
+}
+
+#[test]
+fn duplicates_typed_modules() {
+    let module: syn::ItemMod = parse_quote! {
+        mod contract {
+            fn check() {}
+        }
+    };
+
+    let output = crossroads_core::typed(
+        quote::quote!(Impl = [u8, HashMap<_, _>]),
+        module.to_token_stream(),
+    );
+
+    let expected: syn::File = parse_quote! {
+        mod contract_u8 {
+            #[allow(dead_code)]
+            type Impl = u8;
+            fn check() {}
+        }
+
+        mod contract_hash_map {
+            #[allow(dead_code)]
+            type Impl<T0, T1> = HashMap<T0, T1>;
+            fn check() {}
+        }
+    };
+    assert_eq!(expected.to_token_stream().to_string(), output.to_string());
+}
+
+#[test]
+fn expands_impl_blocks_into_newtypes() {
+    let item: syn::ItemImpl = parse_quote! {
+        impl<T> Backend for Store<T> {
+            fn get(&self) -> usize {
+                match fork!() {
+                    empty => 0,
+                    after_add => self.len(),
+                }
+            }
+        }
+    };
+
+    let output = crossroads_core::crossroads(quote::quote!(), item.to_token_stream());
+
+    let expected: syn::File = parse_quote! {
+        #[allow(non_camel_case_types)]
+        struct Store_Empty<T>(Store<T>);
+
+        impl<T> ::core::ops::Deref for Store_Empty<T> {
+            type Target = Store<T>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl<T> ::core::ops::DerefMut for Store_Empty<T> {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+
+        impl<T> Backend for Store_Empty<T> {
+            fn get(&self) -> usize {
+                {
+                    0
+                }
+            }
+        }
+    };
+    assert!(output
+        .to_string()
+        .starts_with(&expected.to_token_stream().to_string()));
+    assert!(output
+        .to_string()
+        .contains("impl < T > Backend for Store_AfterAdd < T >"));
+}
+
+#[test]
+fn selects_fields_per_arm() {
+    let item: syn::ItemStruct = parse_quote! {
+        struct Config {
+            threads: usize,
+            #[fork(cached)]
+            cache_size: usize,
+        }
+    };
+
+    let output = crossroads_core::crossroads(quote::quote!(), item.to_token_stream());
+
+    let expected: syn::File = parse_quote! {
+        #[allow(non_camel_case_types)]
+        struct Config_Cached {
+            threads: usize,
+            cache_size: usize
+        }
+    };
+    assert_eq!(expected.to_token_stream().to_string(), output.to_string());
+}
+
+#[test]
+fn defers_to_fork_sets() {
+    let function: ItemFn = parse_quote! {
+        fn filled() {
+            let items = fork!(use sets::collections);
+        }
+    };
+
+    let output = crossroads_core::crossroads(quote::quote!(all), function.to_token_stream());
+    assert!(output
+        .to_string()
+        .starts_with("sets :: collections ! { { :: crossroads :: __resolve_fork_set } [sets :: collections] (all)"));
+
+    let resolved = crossroads_core::resolve_fork_set(quote::quote! {
+        [sets::collections] () { #function } { vec => Vec::new(), deque => VecDeque::new() }
+    });
+    assert!(resolved.to_string().contains("fn filled_deque"));
+}
+
+#[test]
+fn adds_test_attributes() {
+    let function: ItemFn = parse_quote! {
+        async fn fetched() {
+            match fork!() {
+                cached => {}
+                remote => {}
+            }
+        }
+    };
+
+    let output = crossroads_core::test(
+        quote::quote!(async_runtime = tokio::test, max_paths = 2),
+        function.to_token_stream(),
+    );
+    let output = output.to_string();
+    assert!(output.contains("# [tokio :: test] async fn fetched_cached ()"));
+    assert!(output.contains("# [tokio :: test] async fn fetched_remote ()"));
+
+    let output = crossroads_core::test(quote::quote!(), function.to_token_stream()).to_string();
+    assert!(output.contains("# [test] fn fetched_cached ()"));
+    assert!(output.contains(":: crossroads_runtime :: block_on (async move"));
+}
+
+#[test]
+fn rejects_wrapping_async_functions() {
+    let function: ItemFn = parse_quote! {
+        async fn fetched() {
+            match fork!() {
+                cached => {}
+                remote => {}
+            }
+        }
+    };
+
+    let output = crossroads_core::crossroads(
+        quote::quote!(wrap_with = loom::model),
+        function.to_token_stream(),
+    );
+    assert!(output
+        .to_string()
+        .contains("#[crossroads(wrap_with = ..)] cannot be used with async functions."));
+}
+
+#[test]
+fn wraps_paths_in_proptest_blocks() {
+    let function: ItemFn = parse_quote! {
+        #[test]
+        fn bounded(#[strategy(0..100u32)] value: u32, unused: bool) {
+            match fork!() {
+                clamped => value.clamp(10, 20),
+                flagged => if unused { 10 } else { 20 },
+            };
+        }
+    };
+
+    let output = crossroads_core::crossroads(quote::quote!(proptest), function.to_token_stream());
+
+    let expected: syn::File = parse_quote! {
+        ::proptest::proptest! {
+            #[test]
+            fn bounded_clamped(value in 0..100u32, _ in ::proptest::arbitrary::any::<bool>()) {
+                {
+                    value.clamp(10, 20)
+                };
+            }
+        }
+    };
+    assert!(output
+        .to_string()
+        .contains(&expected.to_token_stream().to_string()));
+}
+
+#[test]
+fn registers_benchmarks() {
+    let function: ItemFn = parse_quote! {
+        pub fn filled() {
+            match fork!() {
+                empty => {}
+                full => {}
+            }
+        }
+    };
+
+    let output =
+        crossroads_core::crossroads(quote::quote!(bench = criterion), function.to_token_stream());
+    let expected: syn::File = parse_quote! {
+        pub fn filled(criterion: &mut ::criterion::Criterion) {
+            fn filled_empty() {
+                {
+                    {}
+                }
+            }
+            fn filled_full() {
+                {
+                    {}
+                }
+            }
+
+            let mut group = criterion.benchmark_group("filled");
+            group.bench_function("empty", |bencher| bencher.iter(filled_empty));
+            group.bench_function("full", |bencher| bencher.iter(filled_full));
+            group.finish();
+        }
+    };
+    assert_eq!(expected.to_token_stream().to_string(), output.to_string());
+
+    let output =
+        crossroads_core::crossroads(quote::quote!(bench = divan), function.to_token_stream());
+    assert!(output
+        .to_string()
+        .contains("# [:: divan :: bench] pub fn full ()"));
+}
+
+#[test]
+fn lists_paths_as_trials() {
+    let function: ItemFn = parse_quote! {
+        fn empty() {
+            match fork!() {
+                #[kind(slow)]
+                by_default => {}
+                #[ignore]
+                after_clear => {}
+            }
+        }
+    };
+
+    let output = crossroads_core::crossroads(quote::quote!(trials), function.to_token_stream());
+    let output = output.to_string();
+    assert!(
+        output.contains("fn empty_trials () -> :: std :: vec :: Vec < :: libtest_mimic :: Trial >")
+    );
+    assert!(output.contains(
+        "Trial :: test (\"empty::by_default\" , | | { empty_by_default () ; :: core :: result :: Result :: Ok (()) }) . with_kind (\"slow\") . with_ignored_flag (false)"
+    ));
+    assert!(output.contains(
+        "Trial :: test (\"empty::after_clear\" , | | { empty_after_clear () ; :: core :: result :: Result :: Ok (()) }) . with_kind (\"\") . with_ignored_flag (true)"
+    ));
+    assert!(!output.contains("# [ignore]"));
+}
+
+#[test]
+fn rejects_trials_for_other_return_types() {
+    let function: ItemFn = parse_quote! {
+        fn counted() -> usize {
+            match fork!() {
+                one => 1,
+                two => 2,
+            }
+        }
+    };
+
+    let output = crossroads_core::crossroads(quote::quote!(trials), function.to_token_stream());
+    assert!(output.to_string().contains("compile_error"));
+    assert!(output.to_string().contains(
+        "#[crossroads(trials)] can only be used with functions that return nothing or a Result."
+    ));
+
+    let function: ItemFn = parse_quote! {
+        fn unit() -> () {
+            match fork!() {
+                one => (),
+                two => (),
+            }
+        }
+    };
+    let output = crossroads_core::crossroads(quote::quote!(trials), function.to_token_stream());
+    assert!(!output.to_string().contains("compile_error"));
+}
@@ -0,0 +1,78 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! Runtime support for the execution modes of the ```crossroads``` proc macro.
+//!
+//! By default, ```#[crossroads]``` generates one function per path and does not need this crate.
+//! The execution modes that keep a single function and visit the paths at runtime, such as
+//! ```#[crossroads(runtime)]```, call into it from the generated code, so it has to be added as a
+//! dependency (or dev-dependency) alongside ```crossroads```. The same goes for ```async fn```s under
+//! ```#[crossroads::test]```, which are run by [block_on] unless another runtime is configured.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+pub use crate::executor::block_on;
+pub use crate::report::{Outcome, Report};
+pub use crate::tracker::Tracker;
+
+mod executor;
+#[cfg(unix)]
+mod process;
+mod report;
+mod tracker;
+
+/// Runs ```body``` once for every path through its fork points, in depth-first order, like
+/// sections in Catch2 or subcases in doctest.
+///
+/// Panics in one path do not prevent the remaining paths from being visited. Once all of them have
+/// been visited, the function panics with a summary of all paths that failed.
+pub fn run<O: Outcome>(function: &'static str, mut body: impl FnMut(&mut Tracker) -> O) {
+    let mut tracker = Tracker::default();
+    let mut report = Report::new(function);
+    loop {
+        match catch_unwind(AssertUnwindSafe(|| body(&mut tracker))) {
+            Ok(outcome) => report.record(tracker.path(function), outcome.into_result()),
+            Err(payload) => report.record(tracker.path(function), Err(report::message(&*payload))),
+        }
+
+        if !tracker.advance() {
+            break;
+        }
+    }
+    report.finish();
+}
+
+/// Runs ```body``` once, but hands off every arm of every fork point to its own child process via
+/// ```fork(2)```, so that everything before a fork point only runs once, without requiring any of the
+/// state to be ```Clone```.
+///
+/// The parent process waits for its children one after the other and collects their results along
+/// with their output (as written to the standard output and error file descriptors). Once all paths have
+/// been visited, the function panics with a summary of all paths that failed.
+///
+/// On platforms without ```fork(2)```, this falls back to [run].
+pub fn run_in_processes<O: Outcome>(function: &'static str, body: impl FnMut(&mut Tracker) -> O) {
+    #[cfg(unix)]
+    process::run(function, body);
+    #[cfg(not(unix))]
+    run(function, body);
+}
@@ -0,0 +1,312 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::os::raw::c_int;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::{env, fs};
+
+use crate::report::{message, Outcome, Report};
+use crate::tracker::Tracker;
+
+/// Separates the fields of a single record in the results file of a child process.
+const FIELD: char = '\u{1f}';
+/// Separates the records in the results file of a child process.
+const RECORD: char = '\u{1e}';
+
+/// The payload used to unwind a process once all arms of a fork point have been handed off to
+/// child processes.
+struct Explored;
+
+/// The result of a single path, as reported back by the process that took it.
+#[derive(Debug)]
+struct Record {
+    path: String,
+    result: Result<(), String>,
+    output: String,
+}
+
+impl Record {
+    fn write(&self, buffer: &mut String) {
+        let (status, message) = match &self.result {
+            Ok(()) => ('0', ""),
+            Err(message) => ('1', message.as_str()),
+        };
+        buffer.extend([
+            &self.path as &str,
+            &FIELD.to_string(),
+            &status.to_string(),
+            &FIELD.to_string(),
+            message,
+            &FIELD.to_string(),
+            &self.output,
+            &RECORD.to_string(),
+        ]);
+    }
+
+    fn read(record: &str) -> Option<Self> {
+        let mut fields = record.splitn(4, FIELD);
+        let path = fields.next()?.to_owned();
+        let status = fields.next()?;
+        let message = fields.next()?.to_owned();
+        let output = fields.next()?.to_owned();
+        Some(Self {
+            path,
+            result: if status == "0" { Ok(()) } else { Err(message) },
+            output,
+        })
+    }
+}
+
+/// A file in the temporary directory that only the current user can access, through which a child
+/// process hands its output and results back to its parent.
+#[derive(Debug)]
+struct TempFile {
+    path: PathBuf,
+    file: fs::File,
+}
+
+impl TempFile {
+    /// Creates a new file, without following symlinks or reusing a file that exists already.
+    fn create(counter: &mut usize, extension: &str) -> io::Result<Self> {
+        loop {
+            *counter += 1;
+            let path = env::temp_dir().join(format!(
+                "crossroads-{}-{}.{}",
+                std::process::id(),
+                counter,
+                extension
+            ));
+            match fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&path)
+            {
+                Ok(file) => return Ok(Self { path, file }),
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Reads everything that has been written to the file by any process and removes it.
+    fn read(mut self) -> String {
+        let mut bytes = Vec::new();
+        let _ = self
+            .file
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| self.file.read_to_end(&mut bytes));
+        let _ = fs::remove_file(&self.path);
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+/// Waits for the given child process to terminate and returns its status.
+// io::Error::other requires Rust 1.74.
+#[allow(clippy::io_other_error)]
+fn wait(child: libc::pid_t) -> io::Result<c_int> {
+    let mut status = 0;
+    loop {
+        match unsafe { libc::waitpid(child, &mut status, 0) } {
+            -1 => {
+                let error = io::Error::last_os_error();
+                if error.kind() != ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+            pid if pid == child => return Ok(status),
+            pid => {
+                return Err(io::Error::new(
+                    ErrorKind::Other,
+                    format!("waitpid returned {} instead of {}", pid, child),
+                ))
+            }
+        }
+    }
+}
+
+/// The state of the current process while exploring the paths through a function via ```fork(2)```.
+#[derive(Debug)]
+pub(crate) struct Process {
+    function: &'static str,
+    /// The arms taken by this process (and its ancestors) so far.
+    segments: Vec<&'static str>,
+    /// Where to write the records to, unless this is the process that started the exploration.
+    results: Option<fs::File>,
+    /// The results of all paths that were taken by this process or its descendants.
+    records: Vec<Record>,
+    counter: usize,
+}
+
+impl Process {
+    fn new(function: &'static str) -> Self {
+        Self {
+            function,
+            segments: Vec::new(),
+            results: None,
+            records: Vec::new(),
+            counter: 0,
+        }
+    }
+
+    pub(crate) fn path(&self) -> String {
+        let mut path = self.function.to_owned();
+        for segment in &self.segments {
+            path.push('_');
+            path.push_str(segment);
+        }
+        path
+    }
+
+    /// Takes every arm in a child process of its own and waits for them one after the other.
+    ///
+    /// Only returns in the child processes, the current process unwinds once all arms have been
+    /// taken.
+    pub(crate) fn fork(&mut self, segments: &'static [&'static str]) -> usize {
+        for (index, segment) in segments.iter().enumerate() {
+            let output = TempFile::create(&mut self.counter, "out")
+                .expect("Failed to create output file for child process!");
+            let results = TempFile::create(&mut self.counter, "results")
+                .expect("Failed to create results file for child process!");
+
+            // Otherwise, anything that is still buffered would be written by the child as well.
+            let _ = std::io::stdout().flush();
+            let _ = std::io::stderr().flush();
+
+            match unsafe { libc::fork() } {
+                -1 => panic!("Failed to fork: {}", std::io::Error::last_os_error()),
+                0 => {
+                    unsafe {
+                        libc::dup2(output.file.as_raw_fd(), libc::STDOUT_FILENO);
+                        libc::dup2(output.file.as_raw_fd(), libc::STDERR_FILENO);
+                    }
+                    self.segments.push(segment);
+                    self.results = Some(results.file);
+                    self.records.clear();
+                    return index;
+                }
+                child => {
+                    let status = wait(child);
+                    self.collect(segment, output.read(), results.read(), status);
+                }
+            }
+        }
+        resume_unwind(Box::new(Explored))
+    }
+
+    /// Collects the records written by the child process that took the given arm.
+    fn collect(
+        &mut self,
+        segment: &str,
+        output: String,
+        results: String,
+        status: io::Result<c_int>,
+    ) {
+        let records: Vec<_> = results.split(RECORD).filter_map(Record::read).collect();
+
+        let reason = match status {
+            // The child may still be running, so its results can not be relied upon.
+            Err(error) => Some(format!("Failed to wait for process: {}.", error)),
+            Ok(_) if !records.is_empty() => None,
+            // The child did not get to write its results, e.g. because it aborted.
+            Ok(status) if libc::WIFSIGNALED(status) => Some(format!(
+                "Process was terminated by signal {}.",
+                libc::WTERMSIG(status)
+            )),
+            Ok(status) => Some(format!(
+                "Process exited with status {}.",
+                libc::WEXITSTATUS(status)
+            )),
+        };
+        if let Some(reason) = reason {
+            self.records.push(Record {
+                path: format!("{}_{}", self.path(), segment),
+                result: Err(reason),
+                output,
+            });
+        } else {
+            for mut record in records {
+                record.output.insert_str(0, &output);
+                self.records.push(record);
+            }
+        }
+    }
+
+    /// Hands the records over to the parent process (and exits) or reports them, if this is the
+    /// process that started the exploration.
+    fn finish(mut self, result: Option<Result<(), String>>) {
+        if let Some(result) = result {
+            self.records.push(Record {
+                path: self.path(),
+                result,
+                output: String::new(),
+            });
+        }
+
+        if let Some(results) = &mut self.results {
+            let mut buffer = String::new();
+            for record in &self.records {
+                record.write(&mut buffer);
+            }
+            let written = results.write_all(buffer.as_bytes()).is_ok();
+            let _ = std::io::stdout().flush();
+            let _ = std::io::stderr().flush();
+            // Skips any destructors or exit handlers, those are still to be run by the parent.
+            unsafe { libc::_exit(if written { 0 } else { 1 }) };
+        }
+
+        let mut report = Report::new(self.function);
+        for record in self.records {
+            let result = match record.result {
+                Err(message) if !record.output.is_empty() => Err(format!(
+                    "{}\n--- output ---\n{}",
+                    message,
+                    record.output.trim_end()
+                )),
+                result => result,
+            };
+            report.record(record.path, result);
+        }
+        report.finish();
+    }
+}
+
+/// See [crate::run_in_processes].
+pub(crate) fn run<O: Outcome>(function: &'static str, mut body: impl FnMut(&mut Tracker) -> O) {
+    let mut tracker = Tracker::default();
+    tracker.process = Some(Process::new(function));
+    let result = match catch_unwind(AssertUnwindSafe(|| body(&mut tracker))) {
+        Ok(outcome) => Some(outcome.into_result()),
+        Err(payload) if payload.is::<Explored>() => None,
+        Err(payload) => Some(Err(message(&*payload))),
+    };
+    let process = tracker
+        .process
+        .take()
+        .expect("Process state must not be removed!");
+    process.finish(result);
+}
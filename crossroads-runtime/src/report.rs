@@ -0,0 +1,135 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// The result of running a function along a single path.
+pub trait Outcome {
+    /// The outcome of a path that completed without any issues.
+    fn success() -> Self;
+
+    fn into_result(self) -> Result<(), String>;
+}
+
+impl Outcome for () {
+    fn success() -> Self {}
+
+    fn into_result(self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<E: Debug> Outcome for Result<(), E> {
+    fn success() -> Self {
+        Ok(())
+    }
+
+    fn into_result(self) -> Result<(), String> {
+        self.map_err(|error| format!("Error: {:?}", error))
+    }
+}
+
+/// Extracts the message from the payload of a panic.
+pub(crate) fn message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("Box<dyn Any>")
+    }
+}
+
+/// Collects the results of all paths through a function.
+#[derive(Debug)]
+pub struct Report {
+    function: &'static str,
+    total: usize,
+    failures: Vec<(String, String)>,
+}
+
+impl Report {
+    pub fn new(function: &'static str) -> Self {
+        Self {
+            function,
+            total: 0,
+            failures: Vec::new(),
+        }
+    }
+
+    /// Records the result of the path with the given name.
+    pub fn record(&mut self, path: String, result: Result<(), String>) {
+        self.total += 1;
+        if let Err(message) = result {
+            self.failures.push((path, message));
+        }
+    }
+
+    /// Runs ```body``` as the continuation of the path with the given name, catching any panics.
+    ///
+    /// If ```body``` branches off into further paths itself, only those are recorded (unless ```body```
+    /// fails before or after doing so).
+    pub fn branch<O: Outcome>(&mut self, path: &str, body: impl FnOnce(&mut Report) -> O) {
+        let recorded = self.total;
+        match catch_unwind(AssertUnwindSafe(|| body(self))) {
+            Ok(outcome) => {
+                let result = outcome.into_result();
+                if self.total == recorded || result.is_err() {
+                    self.record(path.to_owned(), result);
+                }
+            }
+            Err(payload) => self.record(path.to_owned(), Err(message(&*payload))),
+        }
+    }
+
+    /// The paths that failed so far, along with their messages.
+    pub fn failures(&self) -> &[(String, String)] {
+        &self.failures
+    }
+
+    /// Panics with a summary of all failed paths, if there are any.
+    pub fn finish(self) {
+        if self.failures.is_empty() {
+            return;
+        }
+
+        let width = self
+            .failures
+            .iter()
+            .map(|(path, _)| path.len())
+            .max()
+            .unwrap_or_default();
+        let mut summary = format!(
+            "{} of {} paths through `{}` failed:\n",
+            self.failures.len(),
+            self.total,
+            self.function
+        );
+        for (path, message) in &self.failures {
+            let message = message.replace('\n', &format!("\n  {:width$} | ", "", width = width));
+            summary.push_str(&format!("  {:width$} | {}\n", path, message, width = width));
+        }
+        panic!("{}", summary);
+    }
+}
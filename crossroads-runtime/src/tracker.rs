@@ -0,0 +1,102 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+/// The arm taken at a single fork point during the current run.
+#[derive(Debug)]
+struct Choice {
+    index: usize,
+    segments: &'static [&'static str],
+}
+
+/// Keeps track of the paths through a function that have already been visited.
+///
+/// Every fork point asks the tracker which of its arms to take. Arms are handed out in depth-first
+/// order, so that after each run, [Tracker::advance] can move on to the next unvisited path.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    choices: Vec<Choice>,
+    depth: usize,
+    /// Set if the arms are taken in child processes instead of subsequent runs.
+    #[cfg(unix)]
+    pub(crate) process: Option<crate::process::Process>,
+}
+
+impl Tracker {
+    /// Called at every fork point, with the name segments of its arms. Returns the index of the arm
+    /// to take in the current run.
+    pub fn fork(&mut self, segments: &'static [&'static str]) -> usize {
+        assert!(
+            !segments.is_empty(),
+            "Must have at least one branch in match branches with fork!()!"
+        );
+
+        #[cfg(unix)]
+        if let Some(process) = &mut self.process {
+            return process.fork(segments);
+        }
+
+        if self.depth == self.choices.len() {
+            self.choices.push(Choice { index: 0, segments });
+        }
+
+        let choice = &self.choices[self.depth];
+        assert_eq!(
+            choice.segments, segments,
+            "Encountered different fork points in the same position of two runs. The fork points must not depend on anything but the arms taken earlier."
+        );
+
+        self.depth += 1;
+        choice.index
+    }
+
+    /// The name of the path taken in the current run, i.e. the name of the function that would have
+    /// been generated for it.
+    pub fn path(&self, function: &str) -> String {
+        #[cfg(unix)]
+        if let Some(process) = &self.process {
+            return process.path();
+        }
+
+        let mut path = function.to_owned();
+        for choice in &self.choices[..self.depth] {
+            path.push('_');
+            path.push_str(choice.segments[choice.index]);
+        }
+        path
+    }
+
+    /// Prepares the next run, returns ```false``` if all paths have been visited.
+    pub fn advance(&mut self) -> bool {
+        // Fork points that were not reached during this run (e.g. due to a panic) are forgotten.
+        self.choices.truncate(self.depth);
+        self.depth = 0;
+
+        while let Some(last) = self.choices.last_mut() {
+            if last.index + 1 < last.segments.len() {
+                last.index += 1;
+                return true;
+            }
+            self.choices.pop();
+        }
+        false
+    }
+}
@@ -0,0 +1,99 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crossroads_runtime::Tracker;
+
+#[test]
+fn visits_nested_paths_depth_first() {
+    let mut tracker = Tracker::default();
+    let mut visited = Vec::new();
+
+    loop {
+        match tracker.fork(&["by_default", "after_add"]) {
+            0 => {}
+            _ => {
+                tracker.fork(&["and_remove", "and_clear"]);
+            }
+        }
+        visited.push(tracker.path("empty"));
+
+        if !tracker.advance() {
+            break;
+        }
+    }
+
+    assert_eq!(
+        vec![
+            "empty_by_default",
+            "empty_after_add_and_remove",
+            "empty_after_add_and_clear"
+        ],
+        visited
+    );
+}
+
+#[test]
+fn visits_sequential_forks_as_product() {
+    let mut tracker = Tracker::default();
+    let mut visited = Vec::new();
+
+    loop {
+        let first = tracker.fork(&["a", "b"]);
+        let second = tracker.fork(&["one", "two", "three"]);
+        visited.push((first, second));
+
+        if !tracker.advance() {
+            break;
+        }
+    }
+
+    assert_eq!(
+        vec![(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2)],
+        visited
+    );
+}
+
+#[test]
+fn without_forks_runs_once() {
+    let mut tracker = Tracker::default();
+
+    assert_eq!("plain", tracker.path("plain"));
+    assert!(!tracker.advance());
+}
+
+#[test]
+#[should_panic(expected = "1 of 2 paths through `checked` failed:\n  checked_b | b\n")]
+fn reports_failed_paths() {
+    crossroads_runtime::run("checked", |tracker| match tracker.fork(&["a", "b"]) {
+        0 => {}
+        _ => panic!("b"),
+    });
+}
+
+#[test]
+#[should_panic(expected = "checked_b | Error: \"b\"")]
+fn reports_errors() {
+    crossroads_runtime::run("checked", |tracker| match tracker.fork(&["a", "b"]) {
+        0 => Ok(()),
+        _ => Err("b"),
+    });
+}
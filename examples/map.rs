@@ -44,3 +44,28 @@ fn empty() {
 
     assert!(map.is_empty());
 }
+
+#[crossroads::crossroads]
+#[test]
+fn filled() {
+    use std::collections;
+
+    // Every version of this function uses one of the two map types.
+    type Map = fork_type!(
+        hash = collections::HashMap<String, usize>,
+        btree = collections::BTreeMap<String, usize>
+    );
+    let mut map = Map::default();
+
+    map.insert("Key".to_owned(), 1337);
+    match fork!() {
+        by_insert => {
+            map.insert("Other".to_owned(), 42);
+        }
+        by_replace => {
+            map.insert("Key".to_owned(), 42);
+        }
+    }
+
+    assert!(!map.is_empty());
+}
@@ -106,208 +106,844 @@
 //! test empty_after_add_and_remove ... ok
 //! ```
 //!
+//! # Marking variants as tests
+//!
+//! The attributes below ```#[crossroads]``` are cloned to every generated function, but those above it only apply
+//! to the original function, which no longer exists after the expansion. To not depend on the order,
+//! ```#[crossroads::test]``` takes the same options as ```#[crossroads]``` and adds ```#[test]``` to every
+//! generated function itself. A ```#[test]``` placed above ```#[crossroads]``` is reported as an error (along with a
+//! note that names the actual problem), instead of silently running nothing.
+//!
+//! ```async fn```s are run to completion by ```crossroads_runtime::block_on```, a minimal executor without any
+//! I/O or timers. Use ```#[crossroads::test(async_runtime = tokio::test)]``` (or ```async_std::test```) to have
+//! that attribute added to every generated function instead:
+//!
+//! ```rust
+//! #[crossroads::test]
+//! async fn empty() {
+//!     let mut map = std::collections::HashMap::<String, usize>::new();
+//!     match fork!() {
+//!         by_default => {}
+//!         after_clear => map.clear(),
+//!     }
+//!     assert!(map.is_empty());
+//! }
+//! ```
+//!
+//! # Combining with rstest and test-case
+//!
+//! Parametrized tests from ```rstest``` and ```test-case``` combine with the paths through a function, as long
+//! as ```#[crossroads]``` (or ```#[crossroads::test]```) is placed above their attributes. It generates one
+//! function per path first, each of which carries the ```#[rstest]```, ```#[case(..)]``` and ```#[test_case(..)]```
+//! attributes as well as the attributes of the arguments (```#[case]```, ```#[values(..)]```, ```#[from(..)]```),
+//! and those then generate a module of tests each, i.e. ```extended_pushed::case_1```:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//! use rstest::{fixture, rstest};
+//!
+//! #[fixture]
+//! fn base() -> Vec<usize> {
+//!     vec![1, 2, 3]
+//! }
+//!
+//! #[crossroads]
+//! #[rstest]
+//! #[case(0)]
+//! #[case(5)]
+//! fn extended(base: Vec<usize>, #[case] extra: usize) {
+//!     let mut values = base;
+//!     match fork!() {
+//!         pushed => values.push(extra),
+//!         inserted => values.insert(0, extra),
+//!     }
+//!     assert!(values.contains(&extra));
+//! }
+//! ```
+//!
+//! Fixtures and cases that are only used along some of the paths do not cause warnings about unused variables
+//! in the others. As ```#[rstest]``` and ```#[test_case(..)]``` generate the ```#[test]```s themselves,
+//! ```#[crossroads::test]``` does not add one in their presence. Placed the other way around, they copy the body
+//! into their tests before ```#[crossroads]``` gets to see it, which fails with ```cannot find macro `fork` ```.
+//!
+//! # Property tests
+//!
+//! With ```#[crossroads(proptest)]```, every generated function is wrapped in a ```proptest!``` block of its own,
+//! so that each path gets its own property run (and shrinking). The arguments of the function are the inputs of
+//! the property: they are drawn from the strategy given with ```#[strategy(..)]```, or from ```any::<T>()```
+//! for their type otherwise. A configuration can be given as ```proptest(Config::with_cases(64))```:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! #[crossroads(proptest)]
+//! // #[test]
+//! fn bounded(#[strategy(0..100u32)] value: u32) {
+//!     let bounded = match fork!() {
+//!         clamped => value.clamp(10, 20),
+//!         wrapped => value % 11 + 10,
+//!     };
+//!     proptest::prop_assert!((10..=20).contains(&bounded));
+//! }
+//!
+//! bounded_clamped();
+//! bounded_wrapped();
+//! ```
+//!
+//! The ```proptest``` crate needs to be a dependency, and the functions must not have a return type, as
+//! ```proptest!``` does not support one.
+//!
+//! # Forking on enums
+//!
+//! Instead of plain identifiers, the arms of a ```match fork!()``` may also be paths to enum variants.
+//! The name segment is then derived from the name of the enum and the variant:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! #[derive(Debug, PartialEq)]
+//! enum Mode {
+//!     Fast,
+//!     Safe,
+//! }
+//!
+//! #[crossroads]
+//! fn run() -> usize {
+//!     match fork!() {
+//!         Mode::Fast => 1,
+//!         Mode::Safe => 2,
+//!     }
+//! }
+//!
+//! assert_eq!(1, run_mode_fast());
+//! assert_eq!(2, run_mode_safe());
+//! ```
+//!
+//! If you are interested in the value itself, you can list the variants directly with
+//! ```fork!(Mode::{ .. })```. Every generated function then gets one of them in place of the
+//! ```fork!()```:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! #[derive(Debug, PartialEq)]
+//! enum Mode {
+//!     Fast,
+//!     Safe,
+//! }
+//!
+//! #[crossroads]
+//! fn run() -> Mode {
+//!     let mode = fork!(Mode::{Fast, Safe});
+//!     mode
+//! }
+//!
+//! assert_eq!(Mode::Fast, run_mode_fast());
+//! assert_eq!(Mode::Safe, run_mode_safe());
+//! ```
+//!
+//! # Forking on types
+//!
+//! Fork points can also be placed wherever a type is expected, including the arguments and the return type
+//! of the function. Every generated function then uses one of the types listed in
+//! ```fork_type!(name = Type, ..)```, with the name becoming the segment:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//! use std::collections::{BTreeMap, HashMap};
+//!
+//! #[crossroads]
+//! fn filled() -> usize {
+//!     type Map = fork_type!(hash = HashMap<String, usize>, btree = BTreeMap<String, usize>);
+//!     let mut map = Map::default();
+//!     map.insert("Key".to_owned(), 1337);
+//!     map.len()
+//! }
+//!
+//! assert_eq!(1, filled_hash());
+//! assert_eq!(1, filled_btree());
+//! ```
+//!
+//! As the types differ between the paths, ```fork_type!()``` is only supported when generating a function
+//! per path (with or without ```enum```), but not with the execution modes that keep a single function.
+//! With ```enum```, the signature is shared by all paths, so it can not contain ```fork_type!()```.
+//!
+//! # Instantiating generic parameters
+//!
+//! Generic functions cannot be run by the test harness directly. With ```#[crossroads(types(T = [..]))]```,
+//! a non-generic version is generated for every listed type instead, combined with the paths through the
+//! function. The bounds of ```T``` are kept as ```where``` clauses on the concrete types:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//! use std::str::FromStr;
+//!
+//! #[crossroads(types(T = [u8, u64, String]))]
+//! fn roundtrip<T: Default + ToString + FromStr>() -> bool {
+//!     let value = match fork!() {
+//!         by_default => T::default(),
+//!         by_parse => T::from_str("7").ok().unwrap(),
+//!     };
+//!     value.to_string().parse::<T>().is_ok()
+//! }
+//!
+//! assert!(roundtrip_u8_by_default());
+//! assert!(roundtrip_string_by_parse());
+//! ```
+//!
+//! The name segment consists of the identifiers within the type, e.g. ```Vec<String>``` turns into
+//! ```vec_string```. As ```T::from_str``` turns into ```<u8>::from_str```, the traits used this way need to be in
+//! scope. For the same reason, associated types need to be spelled out as ```<T as Trait>::Type```.
+//!
+//! # Generating families of trait implementations
+//!
+//! ```#[crossroads]``` can also be placed above an impl block, with fork points in any of its methods. For
+//! every path, a newtype around the original type is generated, named after the type and the path (e.g.
+//! ```Store_AfterAdd```), which implements ```Deref``` and ```DerefMut``` to the original type and gets its own
+//! copy of the impl block:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! trait Backend {
+//!     fn get(&self, key: &str) -> Option<usize>;
+//! }
+//!
+//! #[derive(Default)]
+//! struct Store {
+//!     value: usize,
+//! }
+//!
+//! #[crossroads]
+//! impl Backend for Store {
+//!     fn get(&self, key: &str) -> Option<usize> {
+//!         match fork!() {
+//!             missing => None,
+//!             stored => Some(self.value + key.len()),
+//!         }
+//!     }
+//! }
+//!
+//! assert_eq!(None, Store_Missing(Store::default()).get("Key"));
+//! assert_eq!(Some(3), Store_Stored(Store::default()).get("Key"));
+//! ```
+//!
+//! This makes it easy to build families of mocks or stubs from a single source. Note that ```Self``` refers to
+//! the newtype within the generated impl blocks, so constructors such as ```Self { .. }``` need to name the
+//! original type instead.
+//!
+//! # Forking items
+//!
+//! Structs, enums, consts and statics can be forked as well. Fields, variants and the fields of struct
+//! expressions marked with ```#[fork(a, b)]``` are only kept in the items for the listed arms, while unmarked
+//! ones are kept in all of them. Fork points in initializers work as usual. Structs and enums are named after
+//! the path in ```PascalCase``` (```Config_Cached```), consts and statics in ```UPPER_CASE``` (```LIMITS_SMALL```):
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! #[crossroads]
+//! #[derive(Default)]
+//! struct Config {
+//!     threads: usize,
+//!     #[fork(cached)]
+//!     cache_size: usize,
+//!     #[fork(cached, checked)]
+//!     checked: bool,
+//! }
+//!
+//! #[crossroads]
+//! const LIMITS: [usize; 2] = match fork!() {
+//!     small => [1, 2],
+//!     large => [100, 200],
+//! };
+//!
+//! let config = Config_Cached { threads: 1, cache_size: LIMITS_LARGE[0], checked: true };
+//! assert_eq!(100, config.cache_size);
+//! assert!(!Config_Checked::default().checked);
+//! assert_eq!([1, 2], LIMITS_SMALL);
+//! ```
+//!
+//! # Running a module against several types
+//!
+//! To run a whole suite of tests against every implementation of a trait (like typed tests in GoogleTest),
+//! place ```#[crossroads::typed(..)]``` above an inline module. It is duplicated once per listed type, named
+//! after the module and the type, with a type alias bound to the type in each copy. Placeholders (```_```)
+//! turn into generic parameters of the alias, and any ```#[crossroads]``` functions within the module expand
+//! as usual:
+//!
+//! ```rust
+//! use std::collections::{BTreeMap, HashMap};
+//!
+//! #[crossroads::typed(Impl = [HashMap<_, _>, BTreeMap<_, _>])]
+//! mod map_contract {
+//!     use super::*;
+//!
+//!     // #[test]
+//!     pub fn insert() {
+//!         let mut map = Impl::<String, usize>::new();
+//!         map.insert("Key".to_owned(), 1337);
+//!         assert_eq!(Some(&1337), map.get("Key"));
+//!     }
+//! }
+//!
+//! # fn main() {
+//! map_contract_hash_map::insert();
+//! map_contract_b_tree_map::insert();
+//! # }
+//! ```
+//!
+//! # Forking on integer values
+//!
+//! For boundary tests, ```fork!(in 0..=3)``` forks on every value of a range of integer literals. The name
+//! segments consist of the name of the variable the fork is bound to and the value. Lists of arbitrary
+//! values are supported as well, and are named by the index of the value. Use ```as name``` to name a fork
+//! point that is not bound to a variable:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! #[crossroads]
+//! fn boundary() -> (usize, usize) {
+//!     let n = fork!(in 0..=3);
+//!     (n, fork!(in [0, usize::MAX - 1, usize::MAX] as edge))
+//! }
+//!
+//! assert_eq!((0, 0), boundary_n_0_edge_0());
+//! assert_eq!((3, usize::MAX), boundary_n_3_edge_2());
+//! ```
+//!
+//! The ranges are evaluated during the expansion, so their bounds have to be integer literals
+//! (negative values are named like ```n_minus_1```), and they may contain at most 1024 values.
+//!
+//! # Forking on the order of statements
+//!
+//! To make sure that the outcome does not depend on the order of some operations, list them in
+//! ```fork_permutations! { .. }```. Every ordering of the statements becomes a path of its own, named after the
+//! statements in that order. Statements are named by their index (```s0```, ```s1```, ..), unless they are
+//! preceded by a ```#[label(name)]```:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//! use std::collections::HashSet;
+//!
+//! #[crossroads]
+//! fn insert() -> usize {
+//!     let mut set = HashSet::new();
+//!     fork_permutations! {
+//!         #[label(first)]
+//!         set.insert(1);
+//!         set.insert(2);
+//!     }
+//!     set.len()
+//! }
+//!
+//! assert_eq!(2, insert_first_s1());
+//! assert_eq!(2, insert_s1_first());
+//! ```
+//!
+//! Similarly, ```fork_subsets! { .. }``` makes each of the statements it contains optional: Every subset of them
+//! becomes a path of its own (keeping their original order), named after the statements it includes, e.g.
+//! ```_with_warm_evict``` or ```_with_none```:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! #[crossroads]
+//! fn cache() -> Vec<&'static str> {
+//!     let mut cache = Vec::new();
+//!     fork_subsets! {
+//!         #[label(warm)]
+//!         cache.push("warm");
+//!         #[label(evict)]
+//!         cache.clear();
+//!     }
+//!     cache
+//! }
+//!
+//! assert_eq!(vec!["warm"], cache_with_warm());
+//! assert!(cache_with_warm_evict().is_empty());
+//! # assert!(cache_with_none().is_empty());
+//! # assert!(cache_with_evict().is_empty());
+//! ```
+//!
+//! For scenarios driven by several actors, ```fork_interleave! { a: { .. }, b: { .. } }``` creates a path for every
+//! interleaving of the given sequences that keeps the order within each of them. The paths are named after the
+//! sequence that takes each step:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! #[crossroads]
+//! fn steps() -> Vec<&'static str> {
+//!     let mut steps = Vec::new();
+//!     fork_interleave! {
+//!         a: {
+//!             steps.push("open");
+//!             steps.push("close");
+//!         },
+//!         b: { steps.push("read") },
+//!     }
+//!     steps
+//! }
+//!
+//! assert_eq!(vec!["open", "close", "read"], steps_a_a_b());
+//! assert_eq!(vec!["open", "read", "close"], steps_a_b_a());
+//! assert_eq!(vec!["read", "open", "close"], steps_b_a_a());
+//! ```
+//!
+//! As the number of orderings grows quickly, at most 8 statements can be permuted, at most 10 can be made
+//! optional and sequences can be interleaved in at most 40320 ways. More generally, ```#[crossroads(max_paths = 5000)]```
+//! refuses to generate functions for more than 5000 paths through a single function, while there is no such limit
+//! by default. There is no sampling of paths beyond that, as every generated function should be reproducible by
+//! its name.
+//!
+//! # Sharing fork points between functions
+//!
+//! Arms that are repeated in many functions can be defined once with ```fork_set!(name { .. })```. Within any
+//! ```#[crossroads]``` item, ```fork!(use name)``` then stands for a ```match fork!() { .. }``` over the arms of the
+//! set, while ```match fork!(name) { .. }``` adds the arms listed in place to them. Sets can be referred to by
+//! path (```fork!(use tests::collections)```), and sets declared with ```pub``` are exported from the crate
+//! root, like any ```#[macro_export]``` macro, so other crates can use them as well:
+//!
+//! ```rust
+//! use std::collections::VecDeque;
+//! use crossroads::{crossroads, fork_set};
+//!
+//! fork_set!(pub collections {
+//!     vec => Vec::new(),
+//!     deque => VecDeque::new(),
+//! });
+//!
+//! #[crossroads]
+//! fn filled() -> usize {
+//!     let mut items = fork!(use collections);
+//!     items.extend([1, 2, 3]);
+//!     items.into_iter().sum()
+//! }
+//!
+//! assert_eq!(6, filled_vec());
+//! assert_eq!(6, filled_deque());
+//! ```
+//!
+//! Like those of ```macro_rules!``` macros, the arms of a set are resolved where it is used, so the types
+//! they refer to need to be in scope there.
+//!
+//! # Setting up and tearing down variants
+//!
+//! Fixture code that every path needs can be moved out of the body with ```#[crossroads(before = setup, after =
+//! teardown)]```. Every generated function then calls ```setup``` first and ```teardown``` once the body is
+//! done, even if it panics or returns early. Both receive the segments of the path of the function:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! fn setup(path: &[&str]) {
+//!     println!("Running {}", path.join("_"));
+//! }
+//!
+//! fn teardown(path: &[&str]) {
+//!     println!("Done with {}", path.join("_"));
+//! }
+//!
+//! fn connect() {}
+//!
+//! fn disconnect() {}
+//!
+//! #[crossroads(before = setup, after = teardown)]
+//! fn empty() {
+//!     match fork!() {
+//!         offline => {}
+//!         #[before(connect)]
+//!         #[after(disconnect)]
+//!         online => {}
+//!     }
+//! }
+//!
+//! empty_offline();
+//! empty_online();
+//! ```
+//!
+//! The ```#[before(hook)]``` and ```#[after(hook)]``` attributes on an arm call functions without arguments
+//! around the body of just that arm, and work with every kind of output. ```before``` and ```after``` are only
+//! supported when generating a function per path.
+//!
+//! # Wrapping the body of variants
+//!
+//! To run every path under a model checker such as ```loom```, a custom runtime or a harness of your own, use
+//! ```#[crossroads(wrap_with = loom::model)]```. The body of every generated function is then handed to the
+//! wrapper as a ```move``` closure, i.e. it turns into ```loom::model(move || { .. })```, and whatever the
+//! wrapper returns is returned from the function. Macros work as well (```wrap_with = my_harness!```). The
+//! closure has the same return type as the function, so ```?``` and ```return``` behave as before:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! fn twice<T>(body: impl Fn() -> T) -> T {
+//!     body();
+//!     body()
+//! }
+//!
+//! #[crossroads(wrap_with = twice)]
+//! fn parsed() -> Result<u8, std::num::ParseIntError> {
+//!     let value = match fork!() {
+//!         valid => "7",
+//!         invalid => "x",
+//!     };
+//!     Ok(value.parse::<u8>()? + 1)
+//! }
+//!
+//! assert_eq!(Ok(8), parsed_valid());
+//! assert!(parsed_invalid().is_err());
+//! ```
+//!
+//! The ```before``` and ```after``` hooks run outside of the wrapper, i.e. only once per function. As the closure
+//! is not async, ```wrap_with``` cannot be used with ```async fn```s.
+//!
+//! # Benchmarking every path
+//!
+//! The same paths can drive benchmarks, so that adding an arm adds a benchmark. With
+//! ```#[crossroads(bench = criterion)]```, a single function taking a ```&mut Criterion``` is generated, which
+//! registers a ```benchmark_group``` named after the function with a ```bench_function``` per path, named
+//! after its segments (```filled/hash_keys_0```). It can be passed to ```criterion_group!``` like any other:
+//!
+//! ```rust
+//! use std::collections::{BTreeMap, HashMap};
+//! use crossroads::crossroads;
+//!
+//! #[crossroads(bench = criterion)]
+//! fn filled() -> usize {
+//!     type Map = fork_type!(hash = HashMap<usize, usize>, btree = BTreeMap<usize, usize>);
+//!
+//!     let mut map = Map::default();
+//!     for key in 0..fork!(in [16, 1024] as keys) {
+//!         map.insert(key, key);
+//!     }
+//!     map.len()
+//! }
+//!
+//! criterion::criterion_group!(benches, filled);
+//! ```
+//!
+//! With ```#[crossroads(bench = divan)]```, a module named after the function is generated instead, with a
+//! ```#[divan::bench]``` function per path. Either way, the function must not take any arguments.
+//!
+//! # Custom test harnesses
+//!
+//! For test targets with ```harness = false```, ```#[crossroads(trials)]``` additionally generates a function
+//! returning one [libtest-mimic](https://docs.rs/libtest-mimic) ```Trial``` per path, named hierarchically after
+//! its segments (```empty::after_add::and_clear```). Arms marked with ```#[ignore]``` turn into ignored trials, and
+//! ```#[kind(name)]``` on an arm tags the trials taking it with that kind:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//! use libtest_mimic::Arguments;
+//!
+//! #[crossroads(trials)]
+//! fn empty() {
+//!     let mut values = vec![1337];
+//!
+//!     match fork!() {
+//!         after_clear => values.clear(),
+//!         #[kind(slow)]
+//!         after_drain => values.drain(..).for_each(drop),
+//!         #[ignore]
+//!         after_nothing => {}
+//!     }
+//!
+//!     assert!(values.is_empty());
+//! }
+//!
+//! let trials = empty_trials();
+//! assert_eq!("empty::after_drain", trials[1].name());
+//! assert_eq!("slow", trials[1].kind());
+//! assert!(trials[2].has_ignored_flag());
+//!
+//! let conclusion = libtest_mimic::run(&Arguments::from_iter(["doctest"]), trials);
+//! assert_eq!(2, conclusion.num_passed);
+//! ```
+//!
+//! The trials run the same functions as the test harness would, so the function must return either nothing or a
+//! ```Result```, whose error is reported for failed trials. See ```tests/trials.rs``` for a complete ```main``` function.
+//!
+//! # Listing the generated functions
+//!
+//! For functions without arguments, ```#[crossroads(variants)]``` additionally emits a constant that lists all
+//! generated functions along with their names. It is named after the original function, so the example
+//! above would also produce:
+//!
+//! ```rust
+//! # fn empty_by_default() {}
+//! # fn empty_after_add_and_remove() {}
+//! # fn empty_after_add_and_clear() {}
+//! const EMPTY_VARIANTS: &[(&str, fn())] = &[
+//!     ("empty_by_default", empty_by_default),
+//!     ("empty_after_add_and_remove", empty_after_add_and_remove),
+//!     ("empty_after_add_and_clear", empty_after_add_and_clear),
+//! ];
+//! ```
+//!
+//! This allows custom harnesses or benchmark drivers to run every variant without hard-coding their names.
+//! If the function is marked with ```#[test]```, the constant is only available in test builds (just like the
+//! functions it refers to). The constant refers to the functions by their plain names, so it can not be used
+//! for associated functions within impl blocks.
+//!
+//! # Running all paths at once
+//!
+//! Outside of the test harness (e.g. in doctests or examples), ```#[crossroads(all)]``` generates an
+//! additional ```<name>_all``` function. It runs every path, catches any panics, and panics once at the
+//! end with a summary of all paths that failed along with their messages:
+//!
+//! ```rust
+//! use std::collections::HashMap;
+//! use crossroads::crossroads;
+//!
+//! #[crossroads(all)]
+//! fn empty() {
+//!     let mut map: HashMap<String, usize> = Default::default();
+//!
+//!     match fork!() {
+//!         by_default => {}
+//!         after_add => {
+//!             map.insert("Key".to_owned(), 1337);
+//!             match fork!() {
+//!                 and_remove => map.remove("Key"),
+//!                 and_clear => map.clear(),
+//!             };
+//!         }
+//!     }
+//!
+//!     assert!(map.is_empty());
+//! }
+//!
+//! empty_all();
+//! ```
+//!
+//! For functions returning a ```Result```, paths that return an ```Err``` count as failed as well, with the
+//! error in the summary. Any other return values are discarded.
+//!
+//! # Selecting the path at runtime
+//!
+//! With ```#[crossroads(enum)]```, the macro generates an enum with one variant per path instead of one
+//! function per path. The function itself is kept (with all its attributes), but takes the variant as an
+//! additional first argument:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! #[crossroads(enum)]
+//! fn scale(value: usize) -> usize {
+//!     match fork!() {
+//!         by_default => value,
+//!         doubled => 2 * value,
+//!     }
+//! }
+//!
+//! assert_eq!(&[ScalePath::ByDefault, ScalePath::Doubled], ScalePath::ALL);
+//! assert_eq!("scale_doubled", ScalePath::Doubled.name());
+//!
+//! let path: ScalePath = "scale_doubled".parse().unwrap();
+//! assert_eq!(4, path.run(2));
+//! assert_eq!(4, scale(ScalePath::Doubled, 2));
+//! ```
+//!
+//! This allows binaries to pick one of the paths, e.g. from their configuration, while sharing the same
+//! source as the tests.
+//!
+//! # Visiting the paths at runtime
+//!
+//! Generating one copy of the function per path can get expensive for large numbers of paths, both in
+//! compile time and binary size. With ```#[crossroads(runtime)]```, the function body is kept only once.
+//! Instead, each ```fork!()``` asks a tracker which arm to take, and the body is re-run until every path has
+//! been visited, like sections in Catch2 or subcases in doctest. Failing paths do not stop the remaining ones
+//! from running, and the function panics with the names of all failed paths at the end. Note that since the
+//! fork points remain (regular) ```match``` expressions in this mode, all of their arms need to have the same type.
+//!
+//! This mode relies on the ```crossroads-runtime``` crate, which needs to be added as a dependency
+//! alongside ```crossroads```:
+//!
+//! ```rust
+//! use std::collections::HashMap;
+//! use crossroads::crossroads;
+//!
+//! #[crossroads(runtime)]
+//! // #[test]
+//! fn empty() {
+//!     let mut map: HashMap<String, usize> = Default::default();
+//!
+//!     match fork!() {
+//!         by_default => {}
+//!         after_add => {
+//!             map.insert("Key".to_owned(), 1337);
+//!             match fork!() {
+//!                 and_remove => {
+//!                     map.remove("Key");
+//!                 }
+//!                 and_clear => map.clear(),
+//!             };
+//!         }
+//!     }
+//!
+//!     assert!(map.is_empty());
+//! }
+//!
+//! // Runs all three paths.
+//! empty();
+//! ```
+//!
+//! # Sharing expensive setup between paths
+//!
+//! With ```#[crossroads(clone)]```, everything before a fork point only runs once. At every ```fork!()```, each
+//! arm (along with the remainder of the function) continues from its own clone of the local variables, so
+//! all local variables that are still used afterwards need to implement ```Clone```. Just like
+//! ```#[crossroads(runtime)]```, this relies on the ```crossroads-runtime``` crate, and the function panics with
+//! a summary of all failed paths at the end:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! #[crossroads(clone)]
+//! // #[test]
+//! fn shared() {
+//!     // Only runs once..
+//!     let mut values: Vec<usize> = (0..1000).collect();
+//!
+//!     match fork!() {
+//!         untouched => {}
+//!         truncated => {
+//!             values.truncate(10);
+//!             match fork!() {
+//!                 and_cleared => values.clear(),
+//!                 and_extended => values.extend(0..990),
+//!             }
+//!         }
+//!     }
+//!
+//!     assert!(values.len() % 1000 == 0);
+//! }
+//!
+//! // Runs all three paths.
+//! shared();
+//! ```
+//!
+//! To be able to split the function at fork points, the ```fork!()```s need to be used as statements (or to
+//! initialize a ```let``` statement), either directly in the function body or in the arm of another ```fork!()```.
+//! Note that arms that contain further fork points are inlined into the remainder of the function, so variables
+//! declared within them remain in scope afterwards.
+//!
+//! # Forking processes at fork points
+//!
+//! For state that cannot be cloned (e.g. file handles or connections), ```#[crossroads(process)]``` also runs
+//! everything before a fork point only once, but then calls ```fork(2)``` to take each arm in a child process of its
+//! own. The parent waits for the children one after the other and reports the outcome of every path, along with
+//! the output the failing ones wrote to the standard output and error file descriptors:
+//!
+//! ```rust
+//! use crossroads::crossroads;
+//!
+//! #[crossroads(process)]
+//! // #[test]
+//! fn forked() {
+//!     // Only runs once..
+//!     let file = std::fs::File::open("Cargo.toml").unwrap();
+//!
+//!     match fork!() {
+//!         by_metadata => assert!(file.metadata().unwrap().len() > 0),
+//!         by_sync => file.sync_all().unwrap(),
+//!     }
+//! }
+//!
+//! // Runs both paths, each in its own process.
+//! forked();
+//! ```
+//!
+//! Arms that abort or crash the process are reported as failures as well. Keep in mind that forking a
+//! multi-threaded process (such as the default test harness) only duplicates the current thread, and that output
+//! captured by the test harness stays in the child process. On platforms without ```fork(2)```, the function falls back
+//! to the behaviour of ```#[crossroads(runtime)]```.
+//!
 //! # Questions and Answers
 //!
 //! 1. Why did you decide to use the ```match```-based syntax and not implement a new one?
+//!
 //! The main reason for using the ```match``` syntax in the way this crate does is to make it as
 //! compatible as possible with code formattting tools such as ```rustfmt```.
 //! See the ```select!``` macros used in the async context for an example of issues a new syntax can cause.
 
 use proc_macro::TokenStream;
-use std::collections::VecDeque;
-
-use syn::__private::{ToTokens, TokenStream2};
-use syn::spanned::Spanned;
-use syn::visit::Visit;
-use syn::visit_mut::VisitMut;
-use syn::{visit, visit_mut, Block, Expr, ExprBlock, Ident, ItemFn, Pat, Stmt};
-
-type Paths<T> = Vec<Vec<T>>;
-
-struct PathFinder {
-    paths: Paths<String>,
-}
-
-impl PathFinder {
-    fn new(paths: Paths<String>) -> Self {
-        Self { paths }
-    }
-
-    fn into_inner(self) -> Paths<String> {
-        self.paths
-    }
-}
-
-impl<'ast> Visit<'ast> for PathFinder {
-    fn visit_expr(&mut self, expr: &'ast Expr) {
-        if match expr {
-            Expr::Match(mtch) => {
-                match mtch.expr.as_ref() {
-                    Expr::Macro(mac) => {
-                        match mac.mac.path.segments.first() {
-                            Some(segment) if segment.ident == "fork" => {
-                                let mut new_paths = Paths::default();
-                                assert!(!mtch.arms.is_empty(), "Must have at least one branch in match branches with fork!()! {:?}", mtch.span());
-                                for arm in &mtch.arms {
-                                    if let Pat::Ident(ident) = &arm.pat {
-                                        let mut this_paths = self.paths.clone();
-                                        for path in &mut this_paths {
-                                            path.push(ident.ident.to_string());
-                                        }
-
-                                        let mut this_pathfinder = PathFinder::new(this_paths);
-                                        this_pathfinder.visit_expr(arm.body.as_ref());
-
-                                        new_paths.append(&mut this_pathfinder.into_inner());
-                                    } else {
-                                        panic!(
-                                            "Must use only idents with a fork!() match! {:?}",
-                                            arm.span()
-                                        );
-                                    }
-                                }
-
-                                self.paths = new_paths;
-                                false
-                            }
-                            _ => true,
-                        }
-                        // TODO: Proper handling of namespace..
-                        // match mac.mac.path.segments.iter().map(|segment| segment.ident.to_string()).collect::<Vec<String>>().as_ref::<[&str]>() {
-                        //     ["fork"] | ["crossroads", "fork"] => {}
-                        //     _ => {}
-                        // }
-                    }
-                    _ => true,
-                }
-            }
-            _ => true,
-        } {
-            visit::visit_expr(self, expr);
-        }
-    }
-}
-
-struct Rewriter {
-    along_path: VecDeque<String>,
-}
-
-impl Rewriter {
-    fn new(path: impl Into<VecDeque<String>>) -> Self {
-        Self {
-            along_path: path.into(),
-        }
-    }
-}
-
-impl VisitMut for Rewriter {
-    fn visit_expr_mut(&mut self, expr: &mut Expr) {
-        if let Some(mut replacement) = if let Expr::Match(mtch) = &expr {
-            match mtch.expr.as_ref() {
-                Expr::Macro(mac) => {
-                    match mac.mac.path.segments.first() {
-                        Some(segment) if segment.ident == "fork" => {
-                            let current = self
-                                .along_path
-                                .pop_front()
-                                .expect("There should always be enough identifiers in this list.");
-                            assert!(!mtch.arms.is_empty(), "Must have at least one branch in match branches with fork!()! {:?}", mtch.span());
-
-                            let mut ret = None;
-
-                            for arm in &mtch.arms {
-                                if let Pat::Ident(ident) = &arm.pat {
-                                    if ident.ident == current {
-                                        ret = Some(Expr::Block(ExprBlock {
-                                            attrs: mtch.attrs.clone(),
-                                            label: None,
-                                            block: Block {
-                                                brace_token: Default::default(),
-                                                stmts: vec![Stmt::Expr(Expr::clone(
-                                                    arm.body.as_ref(),
-                                                ))],
-                                            },
-                                        }));
-                                        break;
-                                    }
-                                } else {
-                                    panic!(
-                                        "Must use only idents with a fork!() match! {:?}",
-                                        arm.span()
-                                    );
-                                }
-                            }
-
-                            if let Some(ret) = ret {
-                                Some(ret)
-                            } else {
-                                panic!("Did not find identifier {} in corresponding match statement. This is almost certainly a bug, please feel free to report it. {:?}", current, mtch.span());
-                            }
-                        }
-                        _ => None,
-                    }
-                    // TODO: Proper handling of namespace..
-                    // match mac.mac.path.segments.iter().map(|segment| segment.ident.to_string()).collect::<Vec<String>>().as_ref::<[&str]>() {
-                    //     ["fork"] | ["crossroads", "fork"] => {}
-                    //     _ => {}
-                    // }
-                }
-                _ => None,
-            }
-        } else {
-            None
-        } {
-            std::mem::swap(expr, &mut replacement);
-            // This is kind of mean: If the expression that we are putting in place of the match is itself another match,
-            // it gets skipped here (as the recursive method assumes you have already visited the node that you give).
-            // As such, we need to manually recurse in this specific case.
-            self.visit_expr_mut(expr);
-        } else {
-            visit_mut::visit_expr_mut(self, expr);
-        }
-    }
-}
 
 /// An attribute macro that can be placed above ```FnItem```s, i.e. freestanding functions everywhere.
 /// It will replace the function with a set of functions induced by the different paths through the
 /// function along the ```match fork!() { a => { ... }, ... }``` points, where the name of the function is induced by the
 /// sequence of the ```identifier``` specified in the patterns of the ```match``` branches used with the for that specific function instance.
+/// Paths to enum variants (```Mode::Fast => { ... }```) as well as ```fork!(Mode::{Fast, Safe})``` expressions are supported as well.
+/// ```fork!(in 0..=3)``` and ```fork!(in [a, b] as name)``` fork on every value of an integer range or list, while
+/// ```fork_type!(hash = HashMap<K, V>, btree = BTreeMap<K, V>)``` forks on types.
+/// With ```#[crossroads(types(T = [u8, String]))]```, a generic parameter is instantiated with each of the given types.
+/// ```fork_permutations! { ... }``` forks on every ordering of the statements it contains, ```fork_subsets! { ... }```
+/// on every subset of them. ```fork_interleave! { a: { ... }, b: { ... } }``` forks on every interleaving of the sequences.
+/// ```fork!(use name)``` and ```match fork!(name) { ... }``` refer to the arms of a set defined with ```fork_set!```.
+/// ```#[crossroads(before = setup, after = teardown)]``` wraps every generated function in calls to the given hooks,
+/// while ```#[crossroads(wrap_with = loom::model)]``` hands its body to the given function or macro as a closure.
+/// ```#[crossroads(proptest)]``` wraps every generated function in a ```proptest!``` block, with the arguments as inputs.
+/// ```#[crossroads(bench = criterion)]``` and ```#[crossroads(bench = divan)]``` register a benchmark per path instead.
+/// ```#[crossroads(trials)]``` also generates a function listing the paths as libtest-mimic trials, e.g. ```empty_trials()```.
+///
+/// For functions without arguments, ```#[crossroads(variants)]``` emits an additional constant named after the function
+/// (e.g. ```EMPTY_VARIANTS``` for ```fn empty()```) that lists all generated functions along with their names.
+///
+/// With ```#[crossroads(enum)]```, a single function taking an additional ```<Name>Path``` enum argument
+/// is generated instead, see the crate-level documentation for details. ```#[crossroads(runtime)]``` keeps a single
+/// function which visits all paths at runtime (which requires the ```crossroads-runtime``` crate). Similarly,
+/// ```#[crossroads(clone)]``` runs the code before a fork point only once and continues each arm from a clone of the local variables,
+/// while ```#[crossroads(process)]``` continues each arm in a forked child process.
+///
+/// Placed above an impl block, one copy of the block is generated per path, for a newtype named after the path.
+/// Structs, enums, consts and statics are copied per path as well, keeping only the fields and variants marked
+/// with ```#[fork(arm)]``` for the arms along it.
 ///
 /// See the crate-level documentation for a concrete example.
 #[proc_macro_attribute]
-pub fn crossroads(_: TokenStream, input: TokenStream) -> TokenStream {
-    let function = syn::parse_macro_input!(input as ItemFn);
-
-    let name = function.sig.ident.to_string();
-
-    let mut paths = PathFinder::new(vec![vec![name]]);
-    paths.visit_block(&function.block);
-
-    let paths = paths.into_inner();
-
-    let mut new_functions: Vec<ItemFn> = Vec::with_capacity(paths.len());
-
-    for path in paths {
-        let mut path = VecDeque::from(path);
-        path.pop_front();
-        let mut function = function.clone();
+pub fn crossroads(args: TokenStream, input: TokenStream) -> TokenStream {
+    crossroads_core::crossroads(args.into(), input.into()).into()
+}
 
-        let mut new_name = function.sig.ident.to_string();
-        for fork in &path {
-            new_name.push('_');
-            new_name.push_str(fork)
-        }
+/// An attribute macro that can be placed above inline modules, e.g.
+/// ```#[crossroads::typed(Impl = [HashMap<_, _>, BTreeMap<_, _>])] mod map_contract { ... }```.
+/// It will replace the module with one copy per listed type, named after the module and the type
+/// (```map_contract_hash_map``` and ```map_contract_b_tree_map```), each of which contains a type alias
+/// ```Impl``` for its type. Placeholders (```_```) turn into generic parameters of the alias.
+///
+/// See the crate-level documentation for a concrete example.
+#[proc_macro_attribute]
+pub fn typed(args: TokenStream, input: TokenStream) -> TokenStream {
+    crossroads_core::typed(args.into(), input.into()).into()
+}
 
-        function.sig.ident = Ident::new(&new_name, function.sig.ident.span());
+/// Defines a set of fork arms that can be reused in any ```#[crossroads]``` item, e.g.
+/// ```fork_set!(collections { vec => Vec::new(), deque => VecDeque::new() })```.
+/// ```match fork!(collections) {}``` and ```fork!(use collections)``` then expand to a ```match fork!() { .. }```
+/// over its arms. Sets declared with ```pub``` are exported from the crate, so that other crates can use them.
+///
+/// See the crate-level documentation for a concrete example.
+#[proc_macro]
+pub fn fork_set(input: TokenStream) -> TokenStream {
+    crossroads_core::fork_set(input.into()).into()
+}
 
-        let mut rewriter = Rewriter::new(path);
-        rewriter.visit_block_mut(&mut function.block);
-        new_functions.push(function);
-    }
+#[doc(hidden)]
+#[proc_macro]
+pub fn __resolve_fork_set(input: TokenStream) -> TokenStream {
+    crossroads_core::resolve_fork_set(input.into()).into()
+}
 
-    let mut tokens = TokenStream2::new();
-    for function in new_functions {
-        function.to_tokens(&mut tokens);
-    }
-    tokens.into()
+/// An attribute macro that works like ```#[crossroads]```, but also adds ```#[test]``` to every generated function,
+/// so that the order of the attributes does not matter. For ```async fn```s, the attribute given with
+/// ```#[crossroads::test(async_runtime = tokio::test)]``` is added instead. Without it, the body is run by
+/// ```crossroads_runtime::block_on```, a minimal executor (which requires the ```crossroads-runtime``` crate).
+///
+/// See the crate-level documentation for a concrete example.
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, input: TokenStream) -> TokenStream {
+    crossroads_core::test(args.into(), input.into()).into()
 }
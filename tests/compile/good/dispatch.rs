@@ -0,0 +1,59 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use crossroads::crossroads;
+
+#[crossroads(enum)]
+pub fn generic<T: Default + std::fmt::Debug>(mut value: T, (a, b): (u8, u8)) -> String {
+    value = match fork!() {
+        keep => value,
+        reset => T::default(),
+    };
+    format!("{:?} {} {}", value, a, b)
+}
+
+#[crossroads(enum)]
+async fn asynchronous() -> usize {
+    match fork!() {
+        a => 1,
+        b => 2,
+    }
+}
+
+#[crossroads(enum)]
+unsafe fn not_safe(pointer: *const usize) -> usize {
+    *pointer
+        + match fork!() {
+            a => 1,
+            b => 2,
+        }
+}
+
+#[crossroads(enum)]
+fn without_forks() {}
+
+fn main() {
+    let _ = GenericPath::Keep.run(1usize, (2, 3));
+    let _ = AsynchronousPath::A.run();
+    let _ = unsafe { NotSafePath::B.run(&5) };
+    WithoutForksPath::WithoutForks.run();
+}
@@ -20,6 +20,8 @@
  * SOFTWARE.
  */
 
+use std::collections::{BTreeMap, HashMap};
+
 use crossroads::crossroads;
 
 #[test]
@@ -109,3 +111,744 @@ fn preference() {
 
     assert_eq!(8, preference_does_matter());
 }
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Fast,
+    Safe,
+    HighCompat,
+}
+
+#[test]
+fn enum_arms() {
+    #[crossroads]
+    fn run() -> usize {
+        match fork!() {
+            Mode::Fast => 1,
+            Mode::Safe => 2,
+            self::Mode::HighCompat => 3,
+        }
+    }
+
+    assert_eq!(1, run_mode_fast());
+    assert_eq!(2, run_mode_safe());
+    assert_eq!(3, run_mode_high_compat());
+}
+
+#[test]
+fn enum_values() {
+    #[crossroads]
+    fn run() -> Mode {
+        fork!(Mode::{Fast, Safe, HighCompat})
+    }
+
+    assert_eq!(Mode::Fast, run_mode_fast());
+    assert_eq!(Mode::Safe, run_mode_safe());
+    assert_eq!(Mode::HighCompat, run_mode_high_compat());
+}
+
+#[test]
+fn enum_values_with_match() {
+    #[crossroads]
+    fn run() -> (Mode, usize) {
+        let mode = fork!(self::Mode::{Fast, HighCompat});
+        let value = match fork!() {
+            one => 1,
+            two => 2,
+        };
+        (mode, value)
+    }
+
+    assert_eq!((Mode::Fast, 1), run_mode_fast_one());
+    assert_eq!((Mode::Fast, 2), run_mode_fast_two());
+    assert_eq!((Mode::HighCompat, 1), run_mode_high_compat_one());
+    assert_eq!((Mode::HighCompat, 2), run_mode_high_compat_two());
+}
+
+#[test]
+fn registry() {
+    #[crossroads(variants)]
+    fn listed() -> usize {
+        match fork!() {
+            a_1 => 1,
+            a_2 => match fork!() {
+                or_3 => 3,
+                or_4 => 4,
+            },
+        }
+    }
+
+    let variants: Vec<(&str, usize)> = LISTED_VARIANTS
+        .iter()
+        .map(|(name, function)| (*name, function()))
+        .collect();
+
+    assert_eq!(
+        vec![
+            ("listed_a_1", 1),
+            ("listed_a_2_or_3", 3),
+            ("listed_a_2_or_4", 4)
+        ],
+        variants
+    );
+}
+
+#[test]
+fn dispatch() {
+    #[crossroads(enum)]
+    fn dispatched(offset: usize) -> usize {
+        offset
+            + match fork!() {
+                by_default => 0,
+                after_add => match fork!() {
+                    one => 1,
+                    two => 2,
+                },
+            }
+    }
+
+    assert_eq!(
+        &[
+            DispatchedPath::ByDefault,
+            DispatchedPath::AfterAddOne,
+            DispatchedPath::AfterAddTwo
+        ],
+        DispatchedPath::ALL
+    );
+    assert_eq!(
+        "dispatched_after_add_two",
+        DispatchedPath::AfterAddTwo.name()
+    );
+    assert_eq!(
+        Ok(DispatchedPath::AfterAddOne),
+        "dispatched_after_add_one".parse()
+    );
+    assert!("dispatched_after_add".parse::<DispatchedPath>().is_err());
+
+    assert_eq!(10, DispatchedPath::ByDefault.run(10));
+    assert_eq!(12, dispatched(DispatchedPath::AfterAddTwo, 10));
+}
+
+#[test]
+fn all_passing() {
+    #[crossroads(all)]
+    fn passing() {
+        match fork!() {
+            a => {}
+            b => {}
+        }
+    }
+
+    passing_all();
+}
+
+#[test]
+#[should_panic(
+    expected = "2 of 3 paths through `failing` failed:\n  failing_b | b\n  failing_c | c\n"
+)]
+fn all_failing() {
+    #[crossroads(all)]
+    fn failing() {
+        match fork!() {
+            a => {}
+            b => panic!("b"),
+            c => panic!("{}", "c"),
+        }
+    }
+
+    failing_all();
+}
+
+#[test]
+#[should_panic(
+    expected = "1 of 2 paths through `returning` failed:\n  returning_b | Error: \"b\"\n"
+)]
+fn all_results() {
+    #[crossroads(all)]
+    fn returning() -> Result<usize, String> {
+        match fork!() {
+            a => Ok(1),
+            b => Err("b".to_owned()),
+        }
+    }
+
+    returning_all();
+}
+
+#[test]
+fn all_dispatch() {
+    #[crossroads(enum, all)]
+    fn dispatched() -> usize {
+        match fork!() {
+            a => 1,
+            b => 2,
+        }
+    }
+
+    dispatched_all();
+}
+
+#[test]
+#[should_panic(expected = "1 of 2 paths through `sized` failed:\n  sized_u64 | 8\n")]
+fn all_instantiated() {
+    #[crossroads(all, types(T = [u8, u64]))]
+    fn sized<T: Default>() -> T {
+        let size = std::mem::size_of::<T>();
+        if size != 1 {
+            panic!("{}", size);
+        }
+        T::default()
+    }
+
+    sized_all();
+}
+
+#[test]
+fn permutations() {
+    #[crossroads]
+    fn order() -> Vec<usize> {
+        let mut order = Vec::new();
+        fork_permutations! {
+            order.push(0);
+            #[label(one)]
+            order.push(1);
+            order.push(2);
+        }
+        order
+    }
+
+    assert_eq!(vec![0, 1, 2], order_s0_one_s2());
+    assert_eq!(vec![0, 2, 1], order_s0_s2_one());
+    assert_eq!(vec![1, 0, 2], order_one_s0_s2());
+    assert_eq!(vec![1, 2, 0], order_one_s2_s0());
+    assert_eq!(vec![2, 0, 1], order_s2_s0_one());
+    assert_eq!(vec![2, 1, 0], order_s2_one_s0());
+}
+
+#[test]
+fn subsets() {
+    #[crossroads]
+    // Nothing is pushed in the path without any of the statements.
+    #[allow(unused_mut)]
+    fn included() -> Vec<usize> {
+        let mut included = Vec::new();
+        fork_subsets! {
+            #[label(one)]
+            included.push(1);
+            #[label(two)]
+            included.push(2);
+        }
+        included
+    }
+
+    assert_eq!(Vec::<usize>::new(), included_with_none());
+    assert_eq!(vec![1], included_with_one());
+    assert_eq!(vec![2], included_with_two());
+    assert_eq!(vec![1, 2], included_with_one_two());
+}
+
+#[test]
+fn interleavings() {
+    #[crossroads]
+    fn steps() -> Vec<&'static str> {
+        let mut steps = Vec::new();
+        fork_interleave! {
+            a: {
+                steps.push("a1");
+                steps.push("a2");
+            },
+            b: {
+                steps.push("b1");
+                steps.push("b2");
+            },
+        }
+        steps
+    }
+
+    assert_eq!(vec!["a1", "a2", "b1", "b2"], steps_a_a_b_b());
+    assert_eq!(vec!["a1", "b1", "a2", "b2"], steps_a_b_a_b());
+    assert_eq!(vec!["a1", "b1", "b2", "a2"], steps_a_b_b_a());
+    assert_eq!(vec!["b1", "a1", "a2", "b2"], steps_b_a_a_b());
+    assert_eq!(vec!["b1", "a1", "b2", "a2"], steps_b_a_b_a());
+    assert_eq!(vec!["b1", "b2", "a1", "a2"], steps_b_b_a_a());
+}
+
+#[test]
+fn ranges() {
+    #[crossroads(variants)]
+    fn boundary() -> (usize, usize) {
+        let n = fork!(in 0..=2);
+        (n, fork!(in [1, usize::MAX] as edge))
+    }
+
+    assert_eq!((0, 1), boundary_n_0_edge_0());
+    assert_eq!((2, 1), boundary_n_2_edge_0());
+    assert_eq!((1, usize::MAX), boundary_n_1_edge_1());
+    assert_eq!(6, BOUNDARY_VARIANTS.len());
+}
+
+#[test]
+fn suffixed_ranges() {
+    #[crossroads]
+    fn wrapping() -> (usize, u8) {
+        let n = fork!(in 254..=255u8);
+        (std::mem::size_of_val(&n), n.wrapping_add(1))
+    }
+
+    assert_eq!((1, 255), wrapping_n_254());
+    assert_eq!((1, 0), wrapping_n_255());
+}
+
+#[test]
+fn types() {
+    #[crossroads]
+    fn size() -> usize {
+        std::mem::size_of::<fork_type!(small = u8, large = u64)>()
+    }
+
+    assert_eq!(1, size_small());
+    assert_eq!(8, size_large());
+}
+
+#[test]
+fn signature_types() {
+    #[crossroads]
+    fn widened(
+        value: fork_type!(narrow = u8, wide = u16),
+    ) -> fork_type!(to_u32 = u32, to_u64 = u64) {
+        value.into()
+    }
+
+    assert_eq!(1u32, widened_narrow_to_u32(1u8));
+    assert_eq!(2u64, widened_narrow_to_u64(2u8));
+    assert_eq!(300u32, widened_wide_to_u32(300u16));
+    assert_eq!(400u64, widened_wide_to_u64(400u16));
+}
+
+#[test]
+fn generic_types() {
+    trait Codec: Sized {
+        fn encode(&self) -> Vec<u8>;
+        fn decode(bytes: &[u8]) -> Self;
+    }
+
+    impl Codec for u8 {
+        fn encode(&self) -> Vec<u8> {
+            vec![*self]
+        }
+
+        fn decode(bytes: &[u8]) -> Self {
+            bytes[0]
+        }
+    }
+
+    impl Codec for String {
+        fn encode(&self) -> Vec<u8> {
+            self.as_bytes().to_vec()
+        }
+
+        fn decode(bytes: &[u8]) -> Self {
+            String::from_utf8(bytes.to_vec()).unwrap()
+        }
+    }
+
+    #[crossroads(variants, types(T = [u8, String]))]
+    fn roundtrip<T: Codec + Default + PartialEq + std::fmt::Debug>() -> usize {
+        let value = match fork!() {
+            by_default => T::default(),
+            by_decode => T::decode(&[42]),
+        };
+        let bytes: Vec<u8> = value.encode();
+        assert_eq!(value, <T as Codec>::decode(&bytes));
+        bytes.len()
+    }
+
+    assert_eq!(1, roundtrip_u8_by_default());
+    assert_eq!(1, roundtrip_u8_by_decode());
+    assert_eq!(0, roundtrip_string_by_default());
+    assert_eq!(1, roundtrip_string_by_decode());
+    assert_eq!(4, ROUNDTRIP_VARIANTS.len());
+}
+
+crossroads::fork_set!(collections {
+    vec => Vec::new(),
+    deque => VecDeque::new(),
+});
+
+mod sets {
+    crossroads::fork_set!(pub(crate) sizes {
+        one => 1,
+        two => 2,
+    });
+}
+
+#[crossroads::typed(Map = [HashMap<_, _>, BTreeMap<_, _>])]
+mod map_contract {
+    use super::*;
+
+    pub fn name() -> &'static str {
+        std::any::type_name::<Map<String, usize>>()
+    }
+
+    #[crossroads]
+    #[test]
+    fn empty() {
+        let mut map = Map::<String, usize>::new();
+        map.insert("Key".to_owned(), 1337);
+        match fork!() {
+            by_remove => {
+                map.remove("Key");
+            }
+            by_clear => map.clear(),
+        }
+        assert!(map.is_empty());
+    }
+}
+
+#[test]
+fn typed_modules() {
+    assert!(map_contract_hash_map::name().contains("HashMap"));
+    assert!(map_contract_b_tree_map::name().contains("BTreeMap"));
+}
+
+#[test]
+fn impl_blocks() {
+    trait Backend {
+        fn get(&self, key: &str) -> Option<usize>;
+    }
+
+    #[derive(Default)]
+    struct Store {
+        values: HashMap<String, usize>,
+    }
+
+    #[crossroads]
+    impl Backend for Store {
+        fn get(&self, key: &str) -> Option<usize> {
+            match fork!() {
+                parsed => key.parse().ok(),
+                stored => self.values.get(key).copied(),
+            }
+        }
+    }
+
+    let mut store = Store::default();
+    store.values.insert("Key".to_owned(), 1337);
+    assert_eq!(Some(42), Store_Parsed(Store::default()).get("42"));
+    assert_eq!(Some(1337), Store_Stored(store).get("Key"));
+}
+
+#[test]
+fn generic_trait_impl_blocks() {
+    trait Backend<K> {
+        fn get(&self, key: K) -> Option<usize>;
+    }
+
+    #[derive(Default)]
+    struct Store<V> {
+        values: HashMap<String, V>,
+    }
+
+    #[crossroads]
+    impl<K: AsRef<str>, V: Copy + Into<usize>> Backend<K> for Store<V>
+    where
+        K: Clone,
+    {
+        fn get(&self, key: K) -> Option<usize> {
+            match fork!() {
+                parsed => key.as_ref().parse().ok(),
+                stored => self.values.get(key.as_ref()).copied().map(Into::into),
+            }
+        }
+    }
+
+    let mut store = Store::<u8>::default();
+    store.values.insert("Key".to_owned(), 42);
+    assert_eq!(Some(7), Store_Parsed(Store::<u8>::default()).get("7"));
+    assert_eq!(Some(42), Store_Stored(store).get(String::from("Key")));
+}
+
+#[test]
+fn methods() {
+    struct Counter {
+        count: usize,
+    }
+
+    impl Counter {
+        #[crossroads]
+        fn new() -> Self {
+            Counter {
+                count: match fork!() {
+                    empty => 0,
+                    filled => 10,
+                },
+            }
+        }
+
+        #[crossroads]
+        fn run(&self) -> usize {
+            match fork!() {
+                doubled => self.count * 2,
+                squared => self.count * self.count,
+            }
+        }
+    }
+
+    assert_eq!(0, Counter::new_empty().run_doubled());
+    assert_eq!(20, Counter::new_filled().run_doubled());
+    assert_eq!(100, Counter::new_filled().run_squared());
+}
+
+#[test]
+fn items() {
+    #[crossroads]
+    #[derive(Debug, Default, PartialEq)]
+    struct Config {
+        threads: usize,
+        #[fork(cached)]
+        cache_size: usize,
+        #[fork(cached, checked)]
+        checked: bool,
+    }
+
+    #[crossroads]
+    #[derive(Debug, PartialEq)]
+    enum Level {
+        Low,
+        #[fork(extended)]
+        Medium,
+        #[fork(basic)]
+        Fallback,
+    }
+
+    #[crossroads]
+    const LIMITS: [usize; 2] = match fork!() {
+        small => [1, 2],
+        large => [100, 200],
+    };
+
+    #[crossroads]
+    static DEFAULTS: Config_Cached = Config_Cached {
+        threads: 1,
+        #[fork(fast)]
+        cache_size: 64,
+        #[fork(small)]
+        cache_size: 0,
+        checked: true,
+    };
+
+    // Only the cached variant has a cache size.
+    assert_eq!(
+        Config_Checked {
+            threads: 0,
+            checked: false
+        },
+        Config_Checked::default()
+    );
+    assert_eq!(64, DEFAULTS_FAST.cache_size);
+    assert_eq!(0, DEFAULTS_SMALL.cache_size);
+    assert!(DEFAULTS_SMALL.checked);
+
+    assert_ne!(Level_Extended::Low, Level_Extended::Medium);
+    assert_ne!(Level_Basic::Low, Level_Basic::Fallback);
+
+    assert_eq!([1, 2], LIMITS_SMALL);
+    assert_eq!([100, 200], LIMITS_LARGE);
+}
+
+#[test]
+fn fork_sets() {
+    // The arms of a set are resolved where it is used.
+    use std::collections::VecDeque;
+
+    #[crossroads(variants)]
+    fn filled() -> usize {
+        let mut items = fork!(use collections);
+        let size = match fork!(sets::sizes) {
+            none => 0,
+        };
+        for item in 0..size {
+            items.extend([item]);
+        }
+        items.len()
+    }
+
+    assert_eq!(0, filled_vec_none());
+    assert_eq!(1, filled_vec_one());
+    assert_eq!(2, filled_deque_two());
+    assert_eq!(6, FILLED_VARIANTS.len());
+}
+
+thread_local! {
+    static HOOKS: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+fn hook(event: String) {
+    HOOKS.with(|hooks| hooks.borrow_mut().push(event));
+}
+
+fn setup(path: &[&str]) {
+    hook(format!("setup {}", path.join(" ")));
+}
+
+fn teardown(path: &[&str]) {
+    hook(format!("teardown {}", path.join(" ")));
+}
+
+fn fill() {
+    hook("fill".to_owned());
+}
+
+fn drain() {
+    hook("drain".to_owned());
+}
+
+#[test]
+fn hooks() {
+    #[crossroads(before = setup, after = teardown)]
+    fn hooked() -> usize {
+        hook("body".to_owned());
+        match fork!() {
+            #[before(fill)]
+            #[after(drain)]
+            filled => {
+                hook("arm".to_owned());
+                1
+            }
+            failing => panic!("failing"),
+        }
+    }
+
+    assert_eq!(1, hooked_filled());
+    assert!(std::panic::catch_unwind(hooked_failing).is_err());
+
+    HOOKS.with(|hooks| {
+        assert_eq!(
+            vec![
+                "setup filled",
+                "body",
+                "fill",
+                "arm",
+                "drain",
+                "teardown filled",
+                "setup failing",
+                "body",
+                "teardown failing"
+            ],
+            hooks.take()
+        )
+    });
+}
+
+fn twice<T>(body: impl Fn() -> T) -> T {
+    body();
+    body()
+}
+
+macro_rules! traced {
+    ($body:expr) => {{
+        hook("traced".to_owned());
+        ($body)()
+    }};
+}
+
+#[test]
+fn wrappers() {
+    #[crossroads(wrap_with = twice)]
+    fn parsed() -> Result<u8, std::num::ParseIntError> {
+        hook("body".to_owned());
+        let value = match fork!() {
+            valid => "7",
+            invalid => "x",
+        };
+        Ok(value.parse::<u8>()? + 1)
+    }
+
+    #[crossroads(wrap_with = traced!, after = teardown)]
+    fn macros() -> usize {
+        match fork!() {
+            one => 1,
+            two => return 2,
+        }
+    }
+
+    assert_eq!(Ok(8), parsed_valid());
+    assert!(parsed_invalid().is_err());
+    assert_eq!(1, macros_one());
+    assert_eq!(2, macros_two());
+
+    HOOKS.with(|hooks| {
+        assert_eq!(
+            vec![
+                "body",
+                "body",
+                "body",
+                "body",
+                "traced",
+                "teardown one",
+                "traced",
+                "teardown two"
+            ],
+            hooks.take()
+        )
+    });
+}
+
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// Returns pending once before completing, to make the executor wait for a wake-up.
+    struct Yield(bool);
+
+    impl Future for Yield {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                context.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    #[crossroads::test(variants)]
+    fn sync() {
+        let values = match fork!() {
+            single => vec![1],
+            double => vec![1, 2],
+        };
+        assert!(!values.is_empty());
+    }
+
+    #[crossroads::test(variants)]
+    #[should_panic(expected = "failing")]
+    fn panicking() {
+        match fork!() {
+            directly => panic!("failing"),
+            formatted => panic!("{}", "failing"),
+        }
+    }
+
+    #[crossroads::test]
+    async fn asynchronous() -> Result<(), std::num::ParseIntError> {
+        Yield(false).await;
+        let value = match fork!() {
+            one => "1",
+            two => "2",
+        };
+        assert!(value.parse::<u8>()? > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn generates_tests() {
+        assert_eq!(2, SYNC_VARIANTS.len());
+        assert_eq!(2, PANICKING_VARIANTS.len());
+        assert_eq!(Ok(()), asynchronous_two());
+    }
+}
@@ -0,0 +1,109 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! Combines ```#[crossroads]``` with the parametrization of ```rstest```, ```test-case``` and ```proptest```.
+
+use crossroads::crossroads;
+use rstest::{fixture, rstest};
+use test_case::test_case;
+
+#[fixture]
+fn base() -> Vec<usize> {
+    vec![1, 2, 3]
+}
+
+#[crossroads]
+#[rstest]
+#[case(0)]
+#[case(5)]
+fn extended(base: Vec<usize>, #[case] extra: usize) {
+    let mut values = base;
+    match fork!() {
+        pushed => values.push(extra),
+        inserted => values.insert(0, extra),
+    }
+    assert_eq!(4, values.len());
+    assert!(values.contains(&extra));
+}
+
+#[crossroads]
+#[test_case(2, 4 ; "two")]
+#[test_case(3, 6 ; "three")]
+fn doubled(input: usize, expected: usize) {
+    let output = match fork!() {
+        added => input + input,
+        multiplied => input * 2,
+    };
+    assert_eq!(expected, output);
+}
+
+// Each argument is only used along some of the paths.
+#[crossroads]
+#[rstest]
+fn matrix(#[values(1, 2)] count: usize, #[values("a", "bc")] name: &str) {
+    match fork!() {
+        counted => assert!(count > 0),
+        named => assert!(!name.is_empty()),
+    }
+}
+
+#[crossroads::test]
+#[rstest]
+#[case::empty("")]
+#[case::filled("abc")]
+fn trimmed(#[case] input: &str) {
+    let padded = match fork!() {
+        leading => format!(" {}", input),
+        trailing => format!("{} ", input),
+    };
+    assert_eq!(input, padded.trim());
+}
+
+#[crossroads::test]
+#[test_case(1 ; "one")]
+fn negated(input: i64) {
+    let output = match fork!() {
+        minus => -input,
+        subtracted => 0 - input,
+    };
+    assert_eq!(-1, output);
+}
+
+#[crossroads(proptest)]
+#[test]
+fn reversed(#[strategy("[a-z]{0,8}")] word: String, count: u8) {
+    let reversed: String = match fork!() {
+        by_chars => word.chars().rev().collect(),
+        by_bytes => String::from_utf8(word.bytes().rev().collect()).unwrap(),
+        repeated => word.repeat(usize::from(count)).chars().rev().collect(),
+    };
+    proptest::prop_assert!(reversed.len() >= word.len() || count == 0);
+}
+
+#[crossroads::test(proptest(proptest::test_runner::Config::with_cases(16)))]
+fn bounded(#[strategy(0..100u32)] value: u32) {
+    let bounded = match fork!() {
+        clamped => value.clamp(10, 20),
+        wrapped => value % 11 + 10,
+    };
+    proptest::prop_assert!((10..=20).contains(&bounded));
+}
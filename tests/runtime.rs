@@ -0,0 +1,338 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::cell::RefCell;
+
+use crossroads::crossroads;
+
+thread_local! {
+    static VISITED: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+fn visit(segment: &'static str) {
+    VISITED.with(|visited| visited.borrow_mut().push(segment));
+}
+
+fn fail(message: &str) {
+    panic!("{}", message);
+}
+
+#[test]
+fn visits_every_path() {
+    #[crossroads(runtime)]
+    fn empty() {
+        use std::collections::HashMap;
+
+        visit("setup");
+        let mut map = HashMap::<String, usize>::default();
+
+        match fork!() {
+            by_default => visit("by_default"),
+            after_add => {
+                map.insert("Key".to_owned(), 1337);
+                match fork!() {
+                    and_remove => {
+                        map.remove("Key");
+                        visit("and_remove");
+                    }
+                    and_clear => {
+                        map.clear();
+                        visit("and_clear");
+                    }
+                };
+            }
+        }
+
+        assert!(map.is_empty());
+    }
+
+    empty();
+
+    VISITED.with(|visited| {
+        assert_eq!(
+            vec![
+                "setup",
+                "by_default",
+                "setup",
+                "and_remove",
+                "setup",
+                "and_clear"
+            ],
+            visited.take()
+        )
+    });
+}
+
+#[test]
+fn supports_enum_values() {
+    #[derive(Debug, PartialEq)]
+    enum Mode {
+        Fast,
+        Safe,
+    }
+
+    #[crossroads(runtime)]
+    fn modes() {
+        let mode = fork!(Mode::{Fast, Safe});
+        visit(match mode {
+            Mode::Fast => "fast",
+            Mode::Safe => "safe",
+        });
+    }
+
+    modes();
+
+    VISITED.with(|visited| assert_eq!(vec!["fast", "safe"], visited.take()));
+}
+
+#[test]
+#[should_panic(
+    expected = "2 of 3 paths through `failing` failed:\n  failing_a_two | two\n  failing_b     | b\n"
+)]
+fn reports_exact_paths() {
+    #[crossroads(runtime)]
+    fn failing() {
+        match fork!() {
+            a => match fork!() {
+                one => {}
+                two => panic!("two"),
+            },
+            b => panic!("b"),
+        }
+    }
+
+    failing();
+}
+
+#[test]
+#[should_panic(expected = "1 of 2 paths through `fallible` failed:\n  fallible_b | Error: \"b\"\n")]
+fn reports_errors() {
+    #[crossroads(runtime)]
+    fn fallible() -> Result<(), &'static str> {
+        match fork!() {
+            a => Ok(()),
+            b => Err("b"),
+        }
+    }
+
+    fallible();
+}
+
+#[test]
+fn clones_at_fork_points() {
+    #[crossroads(clone)]
+    fn shared() {
+        visit("setup");
+        let mut values = vec![1];
+        let unrelated = "unrelated";
+
+        match fork!() {
+            untouched => {}
+            extended => {
+                values.push(2);
+                match fork!() {
+                    once => {}
+                    twice => values.push(3),
+                }
+            }
+        }
+
+        visit(match values.as_slice() {
+            [1] => "1",
+            [1, 2] => "1, 2",
+            [1, 2, 3] => "1, 2, 3",
+            _ => unreachable!(),
+        });
+        visit(unrelated);
+    }
+
+    shared();
+
+    VISITED.with(|visited| {
+        assert_eq!(
+            vec![
+                "setup",
+                "1",
+                "unrelated",
+                "1, 2",
+                "unrelated",
+                "1, 2, 3",
+                "unrelated"
+            ],
+            visited.take()
+        )
+    });
+}
+
+#[test]
+fn clones_for_let_statements() {
+    #[derive(Clone, Debug, PartialEq)]
+    enum Mode {
+        Fast,
+        Safe,
+    }
+
+    #[crossroads(clone)]
+    fn modes() {
+        let prefix = String::from("mode");
+        let mode = fork!(Mode::{Fast, Safe});
+        let count = match fork!() {
+            one => 1,
+            two => 2,
+        };
+        assert_eq!("mode", prefix);
+        visit(match (mode, count) {
+            (Mode::Fast, 1) => "fast, 1",
+            (Mode::Fast, 2) => "fast, 2",
+            (Mode::Safe, 1) => "safe, 1",
+            (Mode::Safe, 2) => "safe, 2",
+            _ => unreachable!(),
+        });
+    }
+
+    modes();
+
+    VISITED.with(|visited| {
+        assert_eq!(
+            vec!["fast, 1", "fast, 2", "safe, 1", "safe, 2"],
+            visited.take()
+        )
+    });
+}
+
+#[test]
+#[should_panic(
+    expected = "3 of 4 paths through `failing` failed:\n  failing_a_two | two\n  failing_b     | b\n  failing_c     | Error: \"c\"\n"
+)]
+fn clone_reports_exact_paths() {
+    #[crossroads(clone)]
+    fn failing() -> Result<(), &'static str> {
+        match fork!() {
+            a => match fork!() {
+                one => {}
+                two => fail("two"),
+            },
+            b => fail("b"),
+            c => Err("c")?,
+        }
+        Ok(())
+    }
+
+    failing();
+}
+
+#[test]
+#[cfg(unix)]
+fn forks_at_fork_points() {
+    #[crossroads(process)]
+    fn forked() {
+        visit("setup");
+        match fork!() {
+            a => match fork!() {
+                one => visit("a, one"),
+                two => visit("a, two"),
+            },
+            b => visit("b"),
+        }
+    }
+
+    forked();
+
+    // All arms are taken in child processes, only the prefix runs in this one.
+    VISITED.with(|visited| assert_eq!(vec!["setup"], visited.take()));
+}
+
+#[test]
+#[cfg(unix)]
+#[should_panic(
+    expected = "2 of 3 paths through `forking` failed:\n  forking_a_two | Process was terminated by signal 6.\n                | --- output ---\n                | written by two\n  forking_b     | Error: \"b\"\n"
+)]
+fn process_reports_exact_paths() {
+    use std::fs::File;
+    use std::io::Write;
+    use std::mem::ManuallyDrop;
+    use std::os::unix::io::FromRawFd;
+
+    #[crossroads(process)]
+    fn forking() -> Result<(), &'static str> {
+        match fork!() {
+            a => match fork!() {
+                one => {}
+                two => {
+                    // Bypasses the output capturing of the test harness, which does not reach the parent.
+                    let mut stdout = ManuallyDrop::new(unsafe { File::from_raw_fd(1) });
+                    writeln!(stdout, "written by two").unwrap();
+                    std::process::abort();
+                }
+            },
+            b => Err("b")?,
+        }
+        Ok(())
+    }
+
+    forking();
+}
+
+#[test]
+fn clones_for_permutations() {
+    #[crossroads(clone)]
+    fn ordering() {
+        let mut order = String::new();
+        fork_permutations! {
+            order.push('a');
+            order.push('b');
+        }
+        VISITED.with(|visited| {
+            visited
+                .borrow_mut()
+                .push(if order == "ab" { "ab" } else { "ba" })
+        });
+    }
+
+    ordering();
+
+    VISITED.with(|visited| assert_eq!(vec!["ab", "ba"], visited.take()));
+}
+
+#[test]
+fn instantiates_generic_parameters() {
+    #[crossroads(runtime, types(T = [u8, u16]))]
+    fn sizes<T>() {
+        match fork!() {
+            single => visit(if std::mem::size_of::<T>() == 1 {
+                "u8"
+            } else {
+                "u16"
+            }),
+            array => visit(if std::mem::size_of::<[T; 2]>() == 2 {
+                "[u8; 2]"
+            } else {
+                "[u16; 2]"
+            }),
+        }
+    }
+
+    sizes_u8();
+    sizes_u16();
+
+    VISITED.with(|visited| assert_eq!(vec!["u8", "[u8; 2]", "u16", "[u16; 2]"], visited.take()));
+}
@@ -0,0 +1,83 @@
+/*
+ * Copyright (c) 2022 Janosch Reppnow <janoschre+rust@gmail.com>.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! A test target with ```harness = false```, which runs the trials generated with ```#[crossroads(trials)]```.
+
+use std::collections::HashMap;
+
+use crossroads::crossroads;
+use libtest_mimic::{Arguments, Trial};
+
+#[crossroads(trials)]
+fn empty() {
+    let mut map: HashMap<String, usize> = Default::default();
+
+    match fork!() {
+        by_default => map.clear(),
+        #[kind(slow)]
+        after_add => {
+            map.insert("Key".to_owned(), 1337);
+            match fork!() {
+                and_remove => map.remove("Key"),
+                and_clear => map.clear(),
+                #[ignore]
+                #[kind(broken)]
+                and_forget => Some(1337),
+            };
+        }
+    }
+
+    assert!(map.is_empty());
+}
+
+#[crossroads(trials)]
+fn parsed() -> Result<u8, std::num::ParseIntError> {
+    match fork!() {
+        number => "7",
+        padded => " 7",
+    }
+    .trim()
+    .parse()
+}
+
+fn main() {
+    let trials = empty_trials();
+    let names: Vec<(&str, &str, bool)> = trials
+        .iter()
+        .map(|trial| (trial.name(), trial.kind(), trial.has_ignored_flag()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("empty::by_default", "", false),
+            ("empty::after_add::and_remove", "slow", false),
+            ("empty::after_add::and_clear", "slow", false),
+            ("empty::after_add::and_forget", "slow,broken", true),
+        ],
+        names
+    );
+
+    let trials: Vec<Trial> = trials.into_iter().chain(parsed_trials()).collect();
+    let conclusion = libtest_mimic::run(&Arguments::from_args(), trials);
+    assert_eq!(5, conclusion.num_passed);
+    assert_eq!(1, conclusion.num_ignored);
+    conclusion.exit();
+}